@@ -0,0 +1,90 @@
+// Compares loading strings through a buffered reader against the byte-at-a-time loop `String`
+// used to run on unbuffered readers. Run with `cargo bench --bench strings`.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::time::{Duration, Instant};
+
+use proc_macro_sample::{Config, ReadExt, StringEncoding, WriteExt};
+
+const STRINGS: usize = 20_000;
+
+fn time(name: &str, mut f: impl FnMut()) {
+    let runs = 5;
+    let mut best = Duration::MAX;
+
+    for _ in 0..runs {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+
+    println!("{:<40} {:>10.2?}", name, best);
+}
+
+// The loop `String::load` used before requiring `BufRead`: one `read_exact` per byte.
+fn load_bytewise(read: &mut impl Read) -> io::Result<Vec<String>> {
+    let mut len = [0u8; 8];
+    read.read_exact(&mut len)?;
+
+    let mut ret = Vec::new();
+
+    for _ in 0..u64::from_le_bytes(len) {
+        let mut bytes = Vec::new();
+        let mut buf = [0u8; 1];
+
+        loop {
+            read.read_exact(&mut buf)?;
+
+            if buf[0] == 0 {
+                break;
+            }
+
+            bytes.push(buf[0]);
+        }
+
+        ret.push(String::from_utf8(bytes).unwrap());
+    }
+
+    Ok(ret)
+}
+
+fn main() {
+    let strings: Vec<String> = (0..STRINGS)
+        .map(|n| format!("string number {} of the benchmark", n))
+        .collect();
+
+    let path = std::env::temp_dir().join(format!("load-dump-bench-{}", std::process::id()));
+
+    for &strings_enc in [StringEncoding::NulTerminated, StringEncoding::LengthPrefixed].iter() {
+        let cfg = Config::default().with_strings(strings_enc);
+
+        let mut buf = Vec::new();
+        buf.dump_with(&cfg, &strings).unwrap();
+        fs::write(&path, &buf).unwrap();
+
+        println!("{:?}, {} strings, {} bytes", strings_enc, STRINGS, buf.len());
+
+        if strings_enc == StringEncoding::NulTerminated {
+            time("File, byte at a time (old)", || {
+                let loaded = load_bytewise(&mut File::open(&path).unwrap()).unwrap();
+                assert_eq!(strings.len(), loaded.len());
+            });
+        }
+
+        time("BufReader<File>", || {
+            let mut read = BufReader::new(File::open(&path).unwrap());
+            let loaded: Vec<String> = read.load_with(&cfg).unwrap();
+            assert_eq!(strings.len(), loaded.len());
+        });
+
+        time("&[u8]", || {
+            let loaded: Vec<String> = (&buf[..]).load_with(&cfg).unwrap();
+            assert_eq!(strings.len(), loaded.len());
+        });
+
+        println!();
+    }
+
+    fs::remove_file(&path).unwrap();
+}
@@ -1,49 +1,277 @@
-use proc_macro2::{Ident, Span};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
 use syn::*;
 
+use crate::tag::TagRepr;
+
 pub const ATTR_STR: &str = "load_dump";
 
-pub fn filter_never(attr: &Attribute) -> Option<String> {
-    if let Some(Meta::List(MetaList { ident, nested, .. })) = attr.interpret_meta() {
-        if ident != ATTR_STR {
-            return None;
+// Flattens every `#[load_dump(...)]` attribute in `attrs` into the list of its items.
+fn load_dump_items(attrs: &[Attribute]) -> Result<Vec<NestedMeta>> {
+    let mut items = Vec::new();
+
+    for attr in attrs {
+        if !attr.path.is_ident(ATTR_STR) {
+            continue;
+        }
+
+        match attr.parse_meta()? {
+            Meta::List(MetaList { nested, .. }) => items.extend(nested),
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    format!("expected #[{}(...)]", ATTR_STR),
+                ))
+            }
         }
+    }
+
+    Ok(items)
+}
+
+fn unknown_item(item: &NestedMeta, kind: &str) -> Error {
+    let key = match *item {
+        NestedMeta::Meta(ref meta) => meta.name().to_string(),
+        NestedMeta::Literal(..) => return Error::new_spanned(item, "unexpected literal"),
+    };
+
+    Error::new_spanned(
+        item,
+        format!("unknown {} attribute `{}({})`", kind, ATTR_STR, key),
+    )
+}
+
+fn str_lit(lit: &Lit) -> Result<LitStr> {
+    match *lit {
+        Lit::Str(ref lit_str) => Ok(lit_str.clone()),
+        _ => Err(Error::new_spanned(lit, "expected a string literal")),
+    }
+}
+
+fn str_value(lit: &Lit) -> Result<String> {
+    str_lit(lit).map(|lit_str| lit_str.value())
+}
+
+// Attributes accepted on the deriving type itself.
+#[derive(Default)]
+pub struct ContainerAttrs {
+    pub dump_bound: Option<LitStr>,
+    pub load_bound: Option<LitStr>,
+    pub tag_repr: Option<TagRepr>,
+}
+
+impl ContainerAttrs {
+    pub fn parse(ast: &DeriveInput) -> Result<Self> {
+        let mut ret = Self::default();
 
-        for nm in nested.iter() {
-            if let NestedMeta::Meta(Meta::NameValue(MetaNameValue {
-                ref ident, ref lit, ..
-            })) = *nm
-            {
-                if ident == "never" {
-                    if let Lit::Str(ref lit_str) = *lit {
-                        return Some(lit_str.value());
+        for item in load_dump_items(&ast.attrs)? {
+            match item {
+                // bound = "..." is shared by both derives
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    ref ident, ref lit, ..
+                })) if ident == "bound" => {
+                    let bound = str_lit(lit)?;
+
+                    ret.dump_bound = Some(bound.clone());
+                    ret.load_bound = Some(bound);
+                }
+
+                // bound(dump = "...", load = "...")
+                NestedMeta::Meta(Meta::List(MetaList {
+                    ref ident,
+                    ref nested,
+                    ..
+                })) if ident == "bound" => {
+                    for inner in nested.iter() {
+                        match *inner {
+                            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                                ref ident,
+                                ref lit,
+                                ..
+                            })) if ident == "dump" => ret.dump_bound = Some(str_lit(lit)?),
+
+                            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                                ref ident,
+                                ref lit,
+                                ..
+                            })) if ident == "load" => ret.load_bound = Some(str_lit(lit)?),
+
+                            _ => {
+                                return Err(Error::new_spanned(
+                                    inner,
+                                    "expected `dump = \"...\"` or `load = \"...\"`",
+                                ))
+                            }
+                        }
                     }
                 }
+
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    ref ident, ref lit, ..
+                })) if ident == "tag_repr" => ret.tag_repr = Some(TagRepr::parse(&str_lit(lit)?)?),
+
+                _ => return Err(unknown_item(&item, "container")),
+            }
+        }
+
+        if let (
+            Some(..),
+            Data::Struct(DataStruct {
+                ref struct_token, ..
+            }),
+        ) = (ret.tag_repr, &ast.data)
+        {
+            return Err(Error::new_spanned(
+                struct_token,
+                "tag_repr can only be applied to enums",
+            ));
+        }
+
+        Ok(ret)
+    }
+}
+
+// Attributes accepted on enum variants.
+#[derive(Default)]
+pub struct VariantAttrs {
+    pub skip: bool,
+    pub never: Option<String>,
+
+    // `never` variants panic when dumped instead of returning NotSerializable
+    pub panic: bool,
+
+    pub tag: Option<LitInt>,
+}
+
+// Message of `never` variants which do not give their own.
+const NEVER_MESSAGE: &str = "variant marked as never serializable";
+
+impl VariantAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut ret = Self::default();
+        let mut panic = None;
+
+        for item in load_dump_items(attrs)? {
+            match item {
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "skip" => ret.skip = true,
+
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    ref ident, ref lit, ..
+                })) if ident == "never" => ret.never = Some(str_value(lit)?),
+
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "never" => {
+                    ret.never = Some(NEVER_MESSAGE.to_owned())
+                }
+
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "panic" => {
+                    panic = Some(ident.clone())
+                }
+
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    ref ident, ref lit, ..
+                })) if ident == "tag" => match *lit {
+                    Lit::Int(ref lit_int) => ret.tag = Some(lit_int.clone()),
+                    _ => return Err(Error::new_spanned(lit, "expected an integer literal")),
+                },
+
+                _ => return Err(unknown_item(&item, "variant")),
             }
         }
+
+        // `panic` may come before or after `never`, but it needs one
+        if let Some(ident) = panic {
+            if ret.never.is_none() {
+                return Err(Error::new_spanned(
+                    ident,
+                    "`panic` can only be applied to `never` variants",
+                ));
+            }
+
+            ret.panic = true;
+        }
+
+        Ok(ret)
     }
+}
+
+// Attributes accepted on struct and variant fields.
+#[derive(Default)]
+pub struct FieldAttrs {
+    pub skip: bool,
+
+    // encode the integers and lengths in the field as varints, regardless of the config
+    pub varint: bool,
+
+    // byte order of the field, overriding the one in the config
+    pub endian: Option<Ident>,
 
-    None
+    // dump and load the field as an opaque blob, like `Blob`
+    pub bytes: bool,
 }
 
-pub fn has_skip(attr: &Attribute) -> bool {
-    if let Some(Meta::List(MetaList { ident, nested, .. })) = attr.interpret_meta() {
-        if ident != ATTR_STR {
-            return false;
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut ret = Self::default();
+
+        for item in load_dump_items(attrs)? {
+            match item {
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "skip" => ret.skip = true,
+
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "varint" => ret.varint = true,
+
+                NestedMeta::Meta(Meta::Word(ref ident)) if ident == "bytes" => ret.bytes = true,
+
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    ref ident, ref lit, ..
+                })) if ident == "endian" => {
+                    let variant = match &*str_value(lit)? {
+                        "little" => "Little",
+                        "big" => "Big",
+                        _ => {
+                            return Err(Error::new_spanned(
+                                lit,
+                                "expected `endian = \"little\"` or `endian = \"big\"`",
+                            ))
+                        }
+                    };
+
+                    ret.endian = Some(Ident::new(variant, Span::call_site()));
+                }
+
+                _ => return Err(unknown_item(&item, "field")),
+            }
+        }
+
+        Ok(ret)
+    }
+
+    // Expression evaluating to the `&Config` the field must be dumped and loaded with.
+    pub fn render_cfg(&self) -> TokenStream {
+        let mut overrides = Vec::new();
+
+        if self.varint {
+            overrides.push(quote! { with_ints(::proc_macro_sample::IntEncoding::Varint) });
+            overrides.push(quote! { with_lengths(::proc_macro_sample::IntEncoding::Varint) });
         }
 
-        nested.iter().any(|nm| {
-            if let NestedMeta::Meta(Meta::Word(ref attr)) = *nm {
-                attr == "skip"
-            } else {
-                false
+        if let Some(ref endian) = self.endian {
+            overrides.push(quote! { with_endian(::proc_macro_sample::Endian::#endian) });
+        }
+
+        if overrides.is_empty() {
+            quote! {cfg}
+        } else {
+            quote! {
+                &cfg #(.#overrides)*
             }
-        })
-    } else {
-        false
+        }
     }
 }
 
+pub fn has_skip(field: &Field) -> Result<bool> {
+    FieldAttrs::parse(&field.attrs).map(|attrs| attrs.skip)
+}
+
 #[inline]
 pub fn pnum(n: usize) -> Ident {
     Ident::new(&format!("_p{}", n), Span::call_site())
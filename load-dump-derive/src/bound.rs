@@ -0,0 +1,108 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{parse::Parser, punctuated::Punctuated, *};
+
+use crate::attrs::*;
+
+// Collects every identifier found in `ts` which names one of the type parameters in `params`.
+fn collect_params(ts: TokenStream, params: &[Ident], found: &mut Vec<Ident>) {
+    for tt in ts {
+        match tt {
+            TokenTree::Ident(ref id) if params.contains(id) && !found.contains(id) => {
+                found.push(id.clone())
+            }
+            TokenTree::Group(group) => collect_params(group.stream(), params, found),
+            _ => {}
+        }
+    }
+}
+
+fn variant_fields<'a>(
+    data: &'a Data,
+    keep_variant: fn(&VariantAttrs) -> bool,
+) -> Result<Vec<&'a Field>> {
+    match *data {
+        Data::Struct(DataStruct { ref fields, .. }) => Ok(fields.iter().collect()),
+        Data::Enum(DataEnum { ref variants, .. }) => {
+            let mut ret = Vec::new();
+
+            for var in variants {
+                if keep_variant(&VariantAttrs::parse(&var.attrs)?) {
+                    ret.extend(var.fields.iter());
+                }
+            }
+
+            Ok(ret)
+        }
+        Data::Union(..) => Ok(Vec::new()),
+    }
+}
+
+fn filter_skipped(fields: Vec<&Field>, skipped: bool) -> Result<Vec<&Field>> {
+    let mut ret = Vec::new();
+
+    for field in fields {
+        if has_skip(field)? == skipped {
+            ret.push(field);
+        }
+    }
+
+    Ok(ret)
+}
+
+// Fields whose type must implement `Dump`: skipped variants and fields are never written, while
+// `never` variants fail (or panic) before reaching their fields.
+pub fn dump_fields(data: &Data) -> Result<Vec<&Field>> {
+    filter_skipped(
+        variant_fields(data, |attrs| !attrs.skip && attrs.never.is_none())?,
+        false,
+    )
+}
+
+// Fields whose type must implement `Load` (if `skipped` is false) or `Default` (if it is true).
+pub fn load_fields(data: &Data, skipped: bool) -> Result<Vec<&Field>> {
+    filter_skipped(
+        variant_fields(data, |attrs| attrs.never.is_none())?,
+        skipped,
+    )
+}
+
+// Returns a copy of `generics` where every type parameter mentioned by `fields` is bound by
+// `bound`.
+pub fn with_bound(generics: &Generics, fields: &[&Field], bound: &Path) -> Generics {
+    let params: Vec<Ident> = generics.type_params().map(|p| p.ident.clone()).collect();
+
+    let mut found = Vec::new();
+
+    for field in fields {
+        collect_params(field.ty.clone().into_token_stream(), &params, &mut found);
+    }
+
+    let mut generics = generics.clone();
+
+    // keep the declaration order, so that the generated where clause is deterministic
+    let bounded: Vec<_> = params.into_iter().filter(|p| found.contains(p)).collect();
+
+    if !bounded.is_empty() {
+        let where_clause = generics.make_where_clause();
+
+        for param in bounded {
+            where_clause.predicates.push(parse_quote!(#param: #bound));
+        }
+    }
+
+    generics
+}
+
+// Returns a copy of `generics` extended with the user provided predicates in `bound`.
+pub fn with_custom_bound(generics: &Generics, bound: &LitStr) -> Result<Generics> {
+    let predicates = Punctuated::<WherePredicate, Token![,]>::parse_terminated
+        .parse_str(&bound.value())
+        .map_err(|err| Error::new(bound.span(), err))?;
+
+    let mut generics = generics.clone();
+
+    generics.make_where_clause().predicates.extend(predicates);
+
+    Ok(generics)
+}
@@ -3,72 +3,157 @@ use quote::quote;
 use syn::{punctuated::Punctuated, *};
 
 use crate::attrs::*;
+use crate::bound;
+use crate::tag::{self, TagRepr};
 
-fn struct_field(field: &Field) -> Ident {
-    let Field {
-        ident: idopt,
-        attrs,
-        ..
-    } = field;
+fn field_ident(field: &Field) -> Result<&Ident> {
+    field
+        .ident
+        .as_ref()
+        .ok_or_else(|| Error::new_spanned(field, "nameless field detected"))
+}
 
-    if attrs.iter().any(has_skip) {
-        Ident::new("_", Span::call_site())
-    } else {
-        idopt.as_ref().expect("nameless field detected").clone()
+// Name the contents of a named enum field are bound to, so that fields named like the locals of
+// the generated methods (`write`, `cfg`, `size`, ...) do not shadow them.
+fn field_binding(id: &Ident) -> Ident {
+    let id = id.to_string();
+
+    Ident::new(
+        &format!("__field_{}", id.trim_start_matches("r#")),
+        Span::call_site(),
+    )
+}
+
+// Method being generated: `dump()`, which writes the value into `write`, or `size_hint()`, which
+// adds its size to `size`.
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Dump,
+    Size,
+}
+
+// Dumps `value`, a reference to the contents of `field`, unless the field is skipped.
+fn render_field_dump(out: Output, value: TokenStream, field: &Field) -> Result<TokenStream> {
+    let attrs = FieldAttrs::parse(&field.attrs)?;
+
+    if attrs.skip {
+        return Ok(quote! {});
+    }
+
+    let cfg = attrs.render_cfg();
+
+    if attrs.bytes {
+        let bytes = quote! {::std::convert::AsRef::<[u8]>::as_ref(#value)};
+
+        return Ok(match out {
+            Output::Dump => quote! {
+                ::proc_macro_sample::WriteExt::write_bytes(write, #bytes, #cfg)?;
+            },
+            Output::Size => quote! {
+                size += ::proc_macro_sample::Config::bytes_size(#cfg, #bytes.len());
+            },
+        });
     }
+
+    Ok(match out {
+        Output::Dump => quote! {
+            ::proc_macro_sample::Dump::dump(#value, write, #cfg)?;
+        },
+        Output::Size => quote! {
+            size += ::proc_macro_sample::Dump::size_hint(#value, #cfg)?;
+        },
+    })
 }
 
-fn render_enum(name: &Ident, vars: &Punctuated<Variant, Token![,]>) -> TokenStream {
+fn render_enum(
+    out: Output,
+    name: &Ident,
+    vars: &Punctuated<Variant, Token![,]>,
+    repr: TagRepr,
+) -> Result<TokenStream> {
     use std::iter::repeat;
 
-    let vmatches = repeat(name)
-        .zip(vars.iter().enumerate())
-        .map(render_enum_match);
+    let vmatches = repeat((out, name, repr))
+        .zip(tag::variant_tags(vars, repr)?.into_iter().zip(vars))
+        .map(render_enum_match)
+        .collect::<Result<Vec<_>>>()?;
 
-    quote! {
+    Ok(quote! {
         match self {
             #(
                 #vmatches,
             )*
         }
-    }
+    })
 }
 
-fn render_enum_dumps((pos, v): (Index, &Variant)) -> TokenStream {
+fn render_enum_dumps(out: Output, repr: TagRepr, (tag, v): (u64, &Variant)) -> Result<TokenStream> {
     use syn::Fields::*;
-    let fnames: Vec<Ident> = match v.fields {
-        Named(FieldsNamed { ref named, .. }) => named.iter().map(struct_field).collect(),
-        Unnamed(FieldsUnnamed { ref unnamed, .. }) => {
-            unnamed.iter().enumerate().map(tuple_field).collect()
-        }
+    let dumps: Vec<TokenStream> = match v.fields {
+        Named(FieldsNamed { ref named, .. }) => named
+            .iter()
+            .map(|field| {
+                let id = field_binding(field_ident(field)?);
+                render_field_dump(out, quote! {#id}, field)
+            })
+            .collect::<Result<_>>()?,
+        Unnamed(FieldsUnnamed { ref unnamed, .. }) => unnamed
+            .iter()
+            .enumerate()
+            .map(|(n, field)| {
+                let id = pnum(n);
+                render_field_dump(out, quote! {#id}, field)
+            })
+            .collect::<Result<_>>()?,
         Unit => vec![],
     };
 
-    quote! {
-        ::proc_macro_sample::Dump::dump(&(#pos as u32), write)?;
-        #(::proc_macro_sample::Dump::dump(&#fnames, write)?;)*
-    }
+    let tag = match out {
+        Output::Dump => repr.render_dump(tag),
+        Output::Size => repr.render_size(tag),
+    };
+
+    Ok(quote! {
+        #tag
+        #(#dumps)*
+    })
 }
 
-fn render_enum_match((name, (pos, var)): (&Ident, (usize, &Variant))) -> TokenStream {
-    if var.attrs.iter().any(has_skip) {
+fn render_enum_match(
+    ((out, name, repr), (tag, var)): ((Output, &Ident, TagRepr), (u64, &Variant)),
+) -> Result<TokenStream> {
+    let attrs = VariantAttrs::parse(&var.attrs)?;
+
+    if attrs.skip {
         let vname = &var.ident;
-        return quote! {&#name::#vname{..} => {}};
+        return Ok(quote! {&#name::#vname{..} => {}});
     }
 
-    let vhead = render_enum_variant(var);
+    let vhead = render_enum_variant(var)?;
 
-    let vdump = if let Some(msg) = var.attrs.iter().filter_map(filter_never).nth(0) {
+    let vdump = if let Some(msg) = attrs.never {
         let name_str = name.to_string();
         let var_str = var.ident.to_string();
-        quote! {panic!("{}::{} cannot be dumped: {}", #name_str, #var_str, #msg)}
+
+        // only dumping panics, sizing the value just fails
+        if attrs.panic && out == Output::Dump {
+            quote! {panic!("{}::{} cannot be dumped: {}", #name_str, #var_str, #msg)}
+        } else {
+            quote! {
+                return Err(::proc_macro_sample::ErrorKind::NotSerializable {
+                    type_name: #name_str,
+                    variant: #var_str,
+                    message: #msg,
+                }.into())
+            }
+        }
     } else {
-        render_enum_dumps((Index::from(pos), var))
+        render_enum_dumps(out, repr, (tag, var))?
     };
 
-    quote! {&#name::#vhead => {
+    Ok(quote! {&#name::#vhead => {
         #vdump
-    }}
+    }})
 }
 
 fn render_enum_variant(
@@ -77,12 +162,22 @@ fn render_enum_variant(
         ref fields,
         ..
     }: &Variant,
-) -> TokenStream {
+) -> Result<TokenStream> {
     use syn::Fields::*;
-    match *fields {
+    Ok(match *fields {
         Named(FieldsNamed { ref named, .. }) => {
-            let fnames = named.iter().map(struct_field);
-            quote! { #name { #(ref #fnames),* } }
+            let mut fnames = Vec::new();
+
+            // skipped fields are left to `..`
+            for field in named {
+                if !has_skip(field)? {
+                    fnames.push(field_ident(field)?);
+                }
+            }
+
+            let bindings: Vec<_> = fnames.iter().map(|id| field_binding(id)).collect();
+
+            quote! { #name { #(#fnames: ref #bindings,)* .. } }
         }
 
         Unnamed(FieldsUnnamed { ref unnamed, .. }) => {
@@ -91,88 +186,101 @@ fn render_enum_variant(
         }
 
         Unit => quote! {#name},
-    }
+    })
 }
 
-fn render_struct_field_dump(field: &Field) -> TokenStream {
-    if let Field {
-        ident: Some(ref id),
-        ref attrs,
-        ..
-    } = *field
-    {
-        if !attrs.iter().any(has_skip) {
-            quote! {
-                ::proc_macro_sample::Dump::dump(&self.#id, write)?;
-            }
-        } else {
-            quote! {}
-        }
-    } else {
-        panic!("nameless field detected")
-    }
+fn render_struct_field_dump(out: Output, field: &Field) -> Result<TokenStream> {
+    let id = field_ident(field)?;
+
+    render_field_dump(out, quote! {&self.#id}, field)
 }
 
-fn render_tuple_field_dump((pos, field): (usize, &Field)) -> TokenStream {
-    let Field { ref attrs, .. } = *field;
+fn render_tuple_field_dump(out: Output, (pos, field): (usize, &Field)) -> Result<TokenStream> {
     let pos = Index::from(pos);
 
-    if !attrs.iter().any(has_skip) {
-        quote! {
-            ::proc_macro_sample::Dump::dump(&self.#pos, write)?;
-        }
-    } else {
-        quote! {}
-    }
+    render_field_dump(out, quote! {&self.#pos}, field)
 }
 
-fn render_struct_fields(vfields: &Fields) -> TokenStream {
+fn render_struct_fields(out: Output, vfields: &Fields) -> Result<TokenStream> {
     use syn::Fields::*;
-    let dumps: Box<dyn Iterator<Item = TokenStream>> = match *vfields {
-        Named(FieldsNamed { ref named, .. }) => {
-            Box::new(named.iter().map(render_struct_field_dump))
-        }
-        Unnamed(FieldsUnnamed { ref unnamed, .. }) => {
-            Box::new(unnamed.iter().enumerate().map(render_tuple_field_dump))
-        }
-        Unit => return quote! {},
+    let dumps: Vec<TokenStream> = match *vfields {
+        Named(FieldsNamed { ref named, .. }) => named
+            .iter()
+            .map(|field| render_struct_field_dump(out, field))
+            .collect::<Result<_>>()?,
+        Unnamed(FieldsUnnamed { ref unnamed, .. }) => unnamed
+            .iter()
+            .enumerate()
+            .map(|field| render_tuple_field_dump(out, field))
+            .collect::<Result<_>>()?,
+        Unit => return Ok(quote! {}),
     };
 
-    quote! {
+    Ok(quote! {
         #(
             #dumps
         )*
-    }
+    })
 }
 
-fn tuple_field((num, &Field { ref attrs, .. }): (usize, &Field)) -> Ident {
-    if attrs.iter().any(has_skip) {
-        Ident::new("_", Span::call_site())
-    } else {
-        pnum(num)
-    }
-}
-
-pub fn gen(ast: DeriveInput) -> TokenStream {
+pub fn gen(ast: DeriveInput) -> Result<TokenStream> {
     let name = &ast.ident;
-    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let attrs = ContainerAttrs::parse(&ast)?;
 
-    let dump_body = match ast.data {
-        Data::Struct(DataStruct { fields, .. }) => render_struct_fields(&fields),
-        Data::Enum(DataEnum { variants, .. }) => render_enum(name, &variants),
-        Data::Union(_) => panic!("tagged unions are not supported"),
+    let generics = match attrs.dump_bound {
+        Some(ref custom) => bound::with_custom_bound(&ast.generics, custom)?,
+        None => bound::with_bound(
+            &ast.generics,
+            &bound::dump_fields(&ast.data)?,
+            &parse_quote!(::proc_macro_sample::Dump),
+        ),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let render_body = |out| match ast.data {
+        Data::Struct(DataStruct { ref fields, .. }) => render_struct_fields(out, fields),
+        Data::Enum(DataEnum { ref variants, .. }) => {
+            render_enum(out, name, variants, attrs.tag_repr.unwrap_or_default())
+        }
+        Data::Union(DataUnion {
+            ref union_token, ..
+        }) => Err(Error::new_spanned(
+            union_token,
+            "tagged unions are not supported",
+        )),
     };
 
-    quote! {
-        #[allow(unused_qualifications)]
+    let dump_body = render_body(Output::Dump)?;
+    let size_body = render_body(Output::Size)?;
+
+    Ok(quote! {
+        #[allow(unused_qualifications, unused_mut)]
         impl #impl_generics ::proc_macro_sample::Dump for #name #ty_generics #where_clause {
-            fn dump(&self, write: &mut (impl ::std::io::Write + ?Sized)) -> ::proc_macro_sample::Result<()> {
+            fn dump(
+                &self,
+                write: &mut (impl ::std::io::Write + ?Sized),
+                cfg: &::proc_macro_sample::Config,
+            ) -> ::proc_macro_sample::Result<()> {
                 {
                     #dump_body
                 }
 
                 Ok(())
             }
+
+            fn size_hint(
+                &self,
+                cfg: &::proc_macro_sample::Config,
+            ) -> ::proc_macro_sample::Result<u64> {
+                let mut size = 0u64;
+
+                {
+                    #size_body
+                }
+
+                Ok(size)
+            }
         }
-    }
+    })
 }
@@ -4,18 +4,21 @@ extern crate quote;
 extern crate syn;
 
 use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
 
 mod attrs;
+mod bound;
 mod dump;
 mod load;
+mod tag;
 
 #[proc_macro_derive(Dump, attributes(load_dump))]
 pub fn dump_impl(input: TokenStream) -> TokenStream {
     // Parse the input token stream
-    let input = syn::parse(input).unwrap();
+    let input = parse_macro_input!(input as DeriveInput);
 
-    // Build the impl
-    let gen = dump::gen(input);
+    // Build the impl, or a compile_error!() pointing at what went wrong
+    let gen = dump::gen(input).unwrap_or_else(|err| err.to_compile_error());
 
     // Return the generated impl
     gen.into()
@@ -24,11 +27,20 @@ pub fn dump_impl(input: TokenStream) -> TokenStream {
 #[proc_macro_derive(Load, attributes(load_dump))]
 pub fn load_impl(input: TokenStream) -> TokenStream {
     // Parse the string representation
-    let input = syn::parse(input).unwrap();
+    let input = parse_macro_input!(input as DeriveInput);
 
-    // Build the impl
-    let gen = load::gen(input);
+    // Build the impl, or a compile_error!() pointing at what went wrong
+    let gen = load::gen(input, load::Target::Owned).unwrap_or_else(|err| err.to_compile_error());
 
     // Return the generated impl
     gen.into()
 }
+
+#[proc_macro_derive(LoadBorrowed, attributes(load_dump))]
+pub fn load_borrowed_impl(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let gen = load::gen(input, load::Target::Borrowed).unwrap_or_else(|err| err.to_compile_error());
+
+    gen.into()
+}
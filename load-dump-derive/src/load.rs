@@ -3,70 +3,155 @@ use quote::quote;
 use syn::*;
 
 use crate::attrs::*;
+use crate::bound;
+use crate::tag;
 
-fn render_enum_match((name, var): (&Ident, &Variant)) -> TokenStream {
+// Trait being derived: `Load`, or its zero-copy counterpart `LoadBorrowed`.
+#[derive(Clone, Copy)]
+pub enum Target {
+    Owned,
+    Borrowed,
+}
+
+impl Target {
+    fn render_load(self, cfg: TokenStream) -> TokenStream {
+        match self {
+            Target::Owned => quote! {::proc_macro_sample::Load::load(read, #cfg)},
+            Target::Borrowed => {
+                quote! {::proc_macro_sample::LoadBorrowed::load_borrowed(read, #cfg)}
+            }
+        }
+    }
+}
+
+fn render_enum_match((target, (name, var)): (Target, (&Ident, &Variant))) -> Result<TokenStream> {
     let vname = &var.ident;
 
-    if let Some(msg) = var.attrs.iter().filter_map(filter_never).nth(0) {
+    let attrs = VariantAttrs::parse(&var.attrs)?;
+
+    if let Some(msg) = attrs.never {
         let name = name.to_string();
         let vname = vname.to_string();
 
-        quote! {panic!("{}::{} cannot be loaded: {}", #name, #vname, #msg)}
+        if attrs.panic {
+            Ok(quote! {panic!("{}::{} cannot be loaded: {}", #name, #vname, #msg)})
+        } else {
+            Ok(quote! {
+                return Err(::proc_macro_sample::ErrorKind::NotSerializable {
+                    type_name: #name,
+                    variant: #vname,
+                    message: #msg,
+                }.into())
+            })
+        }
     } else {
-        let loads = render_fields(&var.fields);
+        let loads = render_fields(target, Some(vname), &var.fields)?;
 
-        quote! {#name::#vname{#loads}}
+        Ok(quote! {#name::#vname{#loads}})
     }
 }
 
-fn render_fields(fields: &Fields) -> TokenStream {
+// Fields of enum variants are loaded with the variant name in the path of their errors.
+fn render_fields(target: Target, variant: Option<&Ident>, fields: &Fields) -> Result<TokenStream> {
     #[inline]
-    fn gen_loads<T: quote::ToTokens>(skip: bool, id: &T) -> TokenStream {
-        if skip {
-            quote! {#id: std::default::Default::default()}
+    fn gen_loads<T: quote::ToTokens>(
+        target: Target,
+        variant: Option<&Ident>,
+        field: &Field,
+        (id, fname): (&T, String),
+    ) -> Result<TokenStream> {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+
+        if attrs.skip {
+            Ok(quote! {#id: std::default::Default::default()})
         } else {
-            quote! {#id : ::proc_macro_sample::Load::load(read)?}
+            let cfg = attrs.render_cfg();
+
+            let load = if attrs.bytes {
+                quote! {
+                    ::proc_macro_sample::ReadExt::read_bytes(read, #cfg)
+                        .map(::std::convert::From::from)
+                }
+            } else {
+                target.render_load(cfg)
+            };
+
+            let vname = variant.map(|vname| {
+                let vname = vname.to_string();
+
+                quote! {.in_field(#vname)}
+            });
+
+            Ok(quote! {#id : #load.map_err(|err| err.in_field(#fname)#vname)?})
         }
     }
 
     use syn::Fields::*;
-    let loads: Box<dyn Iterator<Item = TokenStream>> = match *fields {
-        Named(FieldsNamed { ref named, .. }) => Box::new(named.iter().map(|field| {
-            if let Field {
-                ident: Some(ref id),
-                ref attrs,
-                ..
-            } = *field
-            {
-                gen_loads(attrs.iter().any(has_skip), id)
-            } else {
-                panic!("nameless field detected")
-            }
-        })),
+    let loads: Vec<TokenStream> = match *fields {
+        Named(FieldsNamed { ref named, .. }) => named
+            .iter()
+            .map(|field| {
+                if let Field {
+                    ident: Some(ref id),
+                    ..
+                } = *field
+                {
+                    gen_loads(target, variant, field, (id, id.to_string()))
+                } else {
+                    Err(Error::new_spanned(field, "nameless field detected"))
+                }
+            })
+            .collect::<Result<_>>()?,
 
-        Unnamed(FieldsUnnamed { ref unnamed, .. }) => Box::new(unnamed.iter().enumerate().map(
-            |(n, &Field { ref attrs, .. })| gen_loads(attrs.iter().any(has_skip), &Index::from(n)),
-        )),
+        Unnamed(FieldsUnnamed { ref unnamed, .. }) => unnamed
+            .iter()
+            .enumerate()
+            .map(|(n, field)| gen_loads(target, variant, field, (&Index::from(n), n.to_string())))
+            .collect::<Result<_>>()?,
 
-        Unit => return quote! {},
+        Unit => return Ok(quote! {}),
     };
 
-    quote! {
+    Ok(quote! {
         #(
             #loads,
         )*
-    }
+    })
 }
 
-pub fn gen(ast: DeriveInput) -> TokenStream {
+pub fn gen(ast: DeriveInput, target: Target) -> Result<TokenStream> {
     use std::iter::repeat;
 
     let name = &ast.ident;
-    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let name_str = name.to_string();
+    let attrs = ContainerAttrs::parse(&ast)?;
+
+    let load_trait: Path = match target {
+        Target::Owned => parse_quote!(::proc_macro_sample::Load),
+        Target::Borrowed => parse_quote!(::proc_macro_sample::LoadBorrowed<'__de>),
+    };
+
+    let generics = match attrs.load_bound {
+        Some(ref custom) => bound::with_custom_bound(&ast.generics, custom)?,
+        None => {
+            let generics = bound::with_bound(
+                &ast.generics,
+                &bound::load_fields(&ast.data, false)?,
+                &load_trait,
+            );
+
+            // skipped fields are filled in with their default value
+            bound::with_bound(
+                &generics,
+                &bound::load_fields(&ast.data, true)?,
+                &parse_quote!(::std::default::Default),
+            )
+        }
+    };
 
     let load_body = match ast.data {
-        Data::Struct(DataStruct { fields, .. }) => {
-            let inside = render_fields(&fields);
+        Data::Struct(DataStruct { ref fields, .. }) => {
+            let inside = render_fields(target, None, fields)?;
 
             quote! {
                 Self {
@@ -75,36 +160,100 @@ pub fn gen(ast: DeriveInput) -> TokenStream {
             }
         }
 
-        Data::Enum(DataEnum { variants, .. }) => {
-            let int_vals = (0..variants.len())
-                .map(Index::from)
-                .map(|idx| quote! {#idx});
+        Data::Enum(DataEnum { ref variants, .. }) => {
+            let repr = attrs.tag_repr.unwrap_or_default();
+            let int_vals = tag::variant_tags(variants, repr)?
+                .into_iter()
+                .map(tag::tag_lit);
+            let load_tag = repr.render_load();
 
-            let loads = repeat(name).zip(&variants).map(render_enum_match);
+            let loads = repeat(target)
+                .zip(repeat(name).zip(variants))
+                .map(render_enum_match)
+                .collect::<Result<Vec<_>>>()?;
 
             quote! {
                 {
-                    let pos : u32 = ::proc_macro_sample::Load::load(read)?;
+                    let pos = #load_tag;
 
                     match pos {
                         #(
                             #int_vals => #loads,
                         )*
-                        _ => panic!("{} is out of enum values range", pos),
+                        _ => return Err(::proc_macro_sample::ErrorKind::InvalidDiscriminant {
+                            type_name: #name_str,
+                            value: pos,
+                        }.into()),
                     }
                 }
             }
         }
 
-        Data::Union(..) => panic!("unions cannot implement Load"),
+        Data::Union(DataUnion {
+            ref union_token, ..
+        }) => {
+            return Err(Error::new_spanned(
+                union_token,
+                "unions cannot implement Load",
+            ))
+        }
     };
 
-    quote! {
-        #[allow(unused_qualifications)]
-        impl #impl_generics ::proc_macro_sample::Load for #name #ty_generics #where_clause {
-            fn load(read: &mut impl std::io::Read) -> ::proc_macro_sample::Result<Self> {
-                Ok(#load_body)
+    let (_, ty_generics, _) = ast.generics.split_for_impl();
+
+    Ok(match target {
+        Target::Owned => {
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+            quote! {
+                #[allow(unused_qualifications, unused_variables)]
+                impl #impl_generics ::proc_macro_sample::Load for #name #ty_generics #where_clause {
+                    fn load(
+                        read: &mut impl std::io::BufRead,
+                        cfg: &::proc_macro_sample::Config,
+                    ) -> ::proc_macro_sample::Result<Self> {
+                        ::proc_macro_sample::load_in_type(#name_str, || {
+                            let cfg = &cfg.descend()?;
+
+                            Ok(#load_body)
+                        })
+                    }
+                }
             }
         }
-    }
+
+        Target::Borrowed => {
+            let generics = with_de_lifetime(generics);
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+            quote! {
+                #[allow(unused_qualifications, unused_variables)]
+                impl #impl_generics #load_trait for #name #ty_generics #where_clause {
+                    fn load_borrowed(
+                        read: &mut &'__de [u8],
+                        cfg: &::proc_macro_sample::Config,
+                    ) -> ::proc_macro_sample::Result<Self> {
+                        ::proc_macro_sample::load_in_type(#name_str, || {
+                            let cfg = &cfg.descend()?;
+
+                            Ok(#load_body)
+                        })
+                    }
+                }
+            }
+        }
+    })
+}
+
+// Adds the `'__de` lifetime of the input slice to `generics`, outliving every lifetime of the
+// type so that its fields can borrow from the input.
+fn with_de_lifetime(mut generics: Generics) -> Generics {
+    let mut de: LifetimeDef = parse_quote!('__de);
+
+    de.bounds
+        .extend(generics.lifetimes().map(|def| def.lifetime.clone()));
+
+    generics.params.insert(0, GenericParam::Lifetime(de));
+
+    generics
 }
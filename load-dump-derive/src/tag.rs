@@ -0,0 +1,154 @@
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+use syn::{punctuated::Punctuated, *};
+
+use crate::attrs::*;
+
+// Integer type used to encode enum tags, from `#[load_dump(tag_repr = "...")]`.
+// Enums without the attribute use the tag encoding of the config they are dumped with.
+#[derive(Clone, Copy)]
+pub enum TagRepr {
+    Config,
+    U8,
+    U16,
+    U32,
+    Varint,
+}
+
+impl Default for TagRepr {
+    fn default() -> Self {
+        TagRepr::Config
+    }
+}
+
+impl TagRepr {
+    pub fn parse(lit: &LitStr) -> Result<Self> {
+        match lit.value().as_str() {
+            "u8" => Ok(TagRepr::U8),
+            "u16" => Ok(TagRepr::U16),
+            "u32" => Ok(TagRepr::U32),
+            "varint" => Ok(TagRepr::Varint),
+            other => Err(Error::new_spanned(
+                lit,
+                format!(
+                    "unknown tag representation `{}`, expected one of u8, u16, u32, varint",
+                    other
+                ),
+            )),
+        }
+    }
+
+    // tags that do not fit the config's width are only detected while dumping
+    fn max_value(self) -> u64 {
+        match self {
+            TagRepr::U8 => u64::from(u8::MAX),
+            TagRepr::U16 => u64::from(u16::MAX),
+            TagRepr::U32 => u64::from(u32::MAX),
+            TagRepr::Config | TagRepr::Varint => u64::MAX,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            TagRepr::Config => "u64",
+            TagRepr::U8 => "u8",
+            TagRepr::U16 => "u16",
+            TagRepr::U32 => "u32",
+            TagRepr::Varint => "varint",
+        }
+    }
+
+    // Expression evaluating to the `TagEncoding` to use.
+    fn render_encoding(self) -> TokenStream {
+        match self {
+            TagRepr::Config => quote! { cfg.tags },
+            TagRepr::U8 => quote! { ::proc_macro_sample::TagEncoding::U8 },
+            TagRepr::U16 => quote! { ::proc_macro_sample::TagEncoding::U16 },
+            TagRepr::U32 => quote! { ::proc_macro_sample::TagEncoding::U32 },
+            TagRepr::Varint => quote! { ::proc_macro_sample::TagEncoding::Varint },
+        }
+    }
+
+    // Statements writing `tag` into `write`.
+    pub fn render_dump(self, tag: u64) -> TokenStream {
+        let tag = tag_lit(tag);
+        let enc = self.render_encoding();
+
+        quote! { ::proc_macro_sample::WriteExt::write_tag(write, #tag, #enc, cfg)?; }
+    }
+
+    // Statements adding the size of `tag` to `size`.
+    pub fn render_size(self, tag: u64) -> TokenStream {
+        let tag = tag_lit(tag);
+        let enc = self.render_encoding();
+
+        quote! { size += ::proc_macro_sample::Config::tag_size(cfg, #tag, #enc)?; }
+    }
+
+    // Expression reading a tag from `read`, as an u64.
+    pub fn render_load(self) -> TokenStream {
+        let enc = self.render_encoding();
+
+        quote! { ::proc_macro_sample::ReadExt::read_tag(read, #enc, cfg)? }
+    }
+}
+
+fn discriminant_value(expr: &Expr) -> Result<u64> {
+    match *expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(ref lit),
+            ..
+        }) => Ok(lit.value()),
+
+        _ => Err(Error::new_spanned(
+            expr,
+            "only integer literal discriminants can be used as tags, use #[load_dump(tag = N)] instead",
+        )),
+    }
+}
+
+// Computes the wire tag of every variant in `vars`, in declaration order.
+// Tags are taken from `#[load_dump(tag = N)]` or from the explicit discriminant of the variant;
+// otherwise, like Rust discriminants, they are one more than the tag of the previous variant.
+pub fn variant_tags(vars: &Punctuated<Variant, Token![,]>, repr: TagRepr) -> Result<Vec<u64>> {
+    let mut tags: Vec<u64> = Vec::with_capacity(vars.len());
+
+    for var in vars {
+        let tag = match (VariantAttrs::parse(&var.attrs)?.tag, &var.discriminant) {
+            (Some(tag), _) => tag.value(),
+            (None, Some((_, ref expr))) => discriminant_value(expr)?,
+            (None, None) => match tags.last() {
+                Some(&prev) => prev.checked_add(1).ok_or_else(|| {
+                    Error::new_spanned(var, "tag overflows, give it an explicit one")
+                })?,
+                None => 0,
+            },
+        };
+
+        if tag > repr.max_value() {
+            return Err(Error::new_spanned(
+                var,
+                format!("tag {} does not fit in {}", tag, repr.name()),
+            ));
+        }
+
+        if let Some(pos) = tags.iter().position(|&other| other == tag) {
+            return Err(Error::new_spanned(
+                var,
+                format!(
+                    "tag {} is already used by variant `{}`",
+                    tag, vars[pos].ident
+                ),
+            ));
+        }
+
+        tags.push(tag);
+    }
+
+    Ok(tags)
+}
+
+#[inline]
+pub fn tag_lit(tag: u64) -> Literal {
+    Literal::u64_unsuffixed(tag)
+}
@@ -0,0 +1,96 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+
+use crate::config::Config;
+use crate::err::Result;
+use crate::ext_io::{Dump, Load, ReadExt, WriteExt};
+
+// Blob is an opaque byte buffer, dumped and loaded as a whole instead of one `u8` at a time.
+// It has the same encoding as a `Vec<u8>`.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Blob(pub Vec<u8>);
+
+impl Deref for Blob {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for Blob {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for Blob {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Blob {
+    fn from(bytes: Vec<u8>) -> Self {
+        Blob(bytes)
+    }
+}
+
+impl From<Blob> for Vec<u8> {
+    fn from(blob: Blob) -> Self {
+        blob.0
+    }
+}
+
+impl Dump for Blob {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_bytes(self, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        Ok(cfg.bytes_size(self.len()))
+    }
+}
+
+impl Load for Blob {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        read.read_bytes(cfg).map(Blob)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Dump for bytes::Bytes {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_bytes(self, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        Ok(cfg.bytes_size(self.len()))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Load for bytes::Bytes {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        read.read_bytes(cfg).map(Self::from)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Dump for bytes::BytesMut {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_bytes(self, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        Ok(cfg.bytes_size(self.len()))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Load for bytes::BytesMut {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        read.read_bytes(cfg)
+            .map(|bytes| Self::from(bytes::Bytes::from(bytes)))
+    }
+}
@@ -0,0 +1,353 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+use std::str;
+
+use crate::blob::Blob;
+use crate::config::*;
+use crate::err::ErrorKind::*;
+use crate::err::*;
+use crate::ext_io::{check_len, check_limit, check_new_key, Load, ReadExt};
+
+// LoadBorrowed is the zero-copy counterpart of `Load`: values are loaded from a cursor over a byte
+// slice, advanced past everything that has been read, and they may borrow from it.
+// Types with lifetimes can derive it, and so can `Load` types meant to be nested in them.
+pub trait LoadBorrowed<'de>: Sized {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self>;
+
+    // load_borrowed_vec() loads a `Vec` of values. Types which never borrow override it to go
+    // through `Load`, which reads primitive types in bulk.
+    fn load_borrowed_vec(read: &mut &'de [u8], cfg: &Config) -> Result<Vec<Self>> {
+        let mut ret = Vec::new();
+
+        load_elems(read, cfg, |_, elem| {
+            ret.push(elem);
+            Ok(())
+        })?;
+
+        Ok(ret)
+    }
+}
+
+// load_elems() reads the length of a collection, then loads its elements one level deeper and
+// passes each of them to `add` along with its index.
+fn load_elems<'de, T: LoadBorrowed<'de>>(
+    read: &mut &'de [u8],
+    cfg: &Config,
+    mut add: impl FnMut(usize, T) -> Result<()>,
+) -> Result<()> {
+    let len = read.read_len(cfg)?;
+
+    check_len::<T>(len, &cfg.limits)?;
+
+    let cfg = cfg.descend()?;
+
+    for n in 0..len {
+        let elem = T::load_borrowed(read, &cfg).map_err(|err| err.at_index(n))?;
+
+        add(n as usize, elem)?;
+    }
+
+    Ok(())
+}
+
+// Types which never borrow from the input load through `Load`.
+macro_rules! load_borrowed_owned {
+    ($($ty:ty)+) => {
+        $(
+            impl<'de> LoadBorrowed<'de> for $ty {
+                fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+                    Self::load(read, cfg)
+                }
+
+                fn load_borrowed_vec(read: &mut &'de [u8], cfg: &Config) -> Result<Vec<Self>> {
+                    Vec::load(read, cfg)
+                }
+            }
+        )*
+    };
+}
+
+load_borrowed_owned!(bool char String Blob);
+load_borrowed_owned!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64);
+load_borrowed_owned!(NonZeroI8 NonZeroI16 NonZeroI32 NonZeroI64 NonZeroI128 NonZeroIsize);
+load_borrowed_owned!(NonZeroU8 NonZeroU16 NonZeroU32 NonZeroU64 NonZeroU128 NonZeroUsize);
+
+#[cfg(feature = "bytes")]
+load_borrowed_owned!(bytes::Bytes bytes::BytesMut);
+
+impl<'de, T: LoadBorrowed<'de>> LoadBorrowed<'de> for Box<T> {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        T::load_borrowed(read, &cfg.descend()?).map(Box::new)
+    }
+}
+
+impl<'de, T: LoadBorrowed<'de>> LoadBorrowed<'de> for Box<[T]> {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        T::load_borrowed_vec(read, cfg).map(Vec::into_boxed_slice)
+    }
+}
+
+impl<'de, T, U> LoadBorrowed<'de> for BTreeMap<T, U>
+where
+    T: LoadBorrowed<'de> + Ord,
+    U: LoadBorrowed<'de>,
+{
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        let mut ret = Self::new();
+
+        load_elems(read, cfg, |n, (key, val)| {
+            check_new_key(ret.insert(key, val).is_none(), n, cfg)
+        })?;
+
+        Ok(ret)
+    }
+}
+
+impl<'de, T: LoadBorrowed<'de> + Ord> LoadBorrowed<'de> for BTreeSet<T> {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        let mut ret = Self::new();
+
+        load_elems(read, cfg, |n, elem| check_new_key(ret.insert(elem), n, cfg))?;
+
+        Ok(ret)
+    }
+}
+
+impl<'de, T, U> LoadBorrowed<'de> for HashMap<T, U>
+where
+    T: LoadBorrowed<'de> + Eq + Hash,
+    U: LoadBorrowed<'de>,
+{
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        let mut ret = Self::new();
+
+        load_elems(read, cfg, |n, (key, val)| {
+            check_new_key(ret.insert(key, val).is_none(), n, cfg)
+        })?;
+
+        Ok(ret)
+    }
+}
+
+impl<'de, T: LoadBorrowed<'de> + Eq + Hash> LoadBorrowed<'de> for HashSet<T> {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        let mut ret = Self::new();
+
+        load_elems(read, cfg, |n, elem| check_new_key(ret.insert(elem), n, cfg))?;
+
+        Ok(ret)
+    }
+}
+
+// Same encoding as `Option<T>` for `Load`, including the strict check of the flag.
+impl<'de, T: LoadBorrowed<'de>> LoadBorrowed<'de> for Option<T> {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        if bool::load_borrowed(read, cfg)? {
+            T::load_borrowed(read, cfg).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'de, T: LoadBorrowed<'de>> LoadBorrowed<'de> for Vec<T> {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        T::load_borrowed_vec(read, cfg)
+    }
+}
+
+macro_rules! load_borrowed_array_len {
+    ($n:literal) => {
+        impl<'de, T: LoadBorrowed<'de>> LoadBorrowed<'de> for [T; $n] {
+            #[allow(clippy::reversed_empty_ranges)]
+            fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+                use arrayvec::ArrayVec;
+
+                let mut arrv = ArrayVec::new();
+
+                for i in 0..$n {
+                    arrv.push(T::load_borrowed(read, cfg).map_err(|err| err.at_index(i))?);
+                }
+
+                arrv.into_inner().map_err(|_| Unknown.into())
+            }
+        }
+    };
+}
+
+macro_rules! load_borrowed_array {
+    ($($n:literal)+) => {
+        $(load_borrowed_array_len!($n);)*
+    };
+}
+
+load_borrowed_array!(0 1 2 3 4 5 6 7 8 9 10 11 12);
+
+macro_rules! load_borrowed_tuple {
+    () => (
+        impl<'de> LoadBorrowed<'de> for () {
+            fn load_borrowed(_: &mut &'de [u8], _: &Config) -> Result<Self> {
+                Ok(())
+            }
+        }
+    );
+
+    ($($name:ident)+) => (
+        impl<'de, $($name: LoadBorrowed<'de>),*> LoadBorrowed<'de> for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+                $(let $name = $name::load_borrowed(read, cfg)?;)*
+                Ok(($($name,)*))
+            }
+        }
+    );
+}
+
+load_borrowed_tuple! {}
+load_borrowed_tuple! { A }
+load_borrowed_tuple! { A B }
+load_borrowed_tuple! { A B C }
+load_borrowed_tuple! { A B C D }
+load_borrowed_tuple! { A B C D E }
+load_borrowed_tuple! { A B C D E F }
+load_borrowed_tuple! { A B C D E F G }
+load_borrowed_tuple! { A B C D E F G H }
+load_borrowed_tuple! { A B C D E F G H I }
+load_borrowed_tuple! { A B C D E F G H I J }
+load_borrowed_tuple! { A B C D E F G H I J K }
+load_borrowed_tuple! { A B C D E F G H I J K L }
+
+// take() splits the first `len` bytes off the cursor.
+fn take<'de>(read: &mut &'de [u8], len: u64) -> Result<&'de [u8]> {
+    if len > read.len() as u64 {
+        return Err(UnexpectedEof.into());
+    }
+
+    let (head, tail) = read.split_at(len as usize);
+    *read = tail;
+
+    Ok(head)
+}
+
+// Same encoding as `Vec<u8>`.
+impl<'de: 'a, 'a> LoadBorrowed<'de> for &'a [u8] {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        let len = read.read_len(cfg)?;
+
+        check_limit(len, cfg.limits.max_len, "max_len")?;
+
+        take(read, len)
+    }
+}
+
+// Same encoding as `String`.
+impl<'de: 'a, 'a> LoadBorrowed<'de> for &'a str {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        let len = match cfg.strings {
+            StringEncoding::NulTerminated => {
+                let len = read.iter().position(|&b| b == 0).ok_or(UnexpectedEof)?;
+
+                len as u64
+            }
+
+            StringEncoding::LengthPrefixed => read.read_len(cfg)?,
+        };
+
+        check_limit(len, cfg.limits.max_string, "max_string")?;
+
+        let bytes = take(read, len)?;
+
+        if cfg.strings == StringEncoding::NulTerminated {
+            // skip the NUL
+            *read = &read[1..];
+        }
+
+        str::from_utf8(bytes).context(InvalidUtf8)
+    }
+}
+
+// Always loaded as `Cow::Borrowed`.
+impl<'de: 'a, 'a> LoadBorrowed<'de> for Cow<'a, str> {
+    fn load_borrowed(read: &mut &'de [u8], cfg: &Config) -> Result<Self> {
+        <&str>::load_borrowed(read, cfg).map(Cow::Borrowed)
+    }
+}
+
+pub fn from_slice<'de, T: LoadBorrowed<'de>>(bytes: &'de [u8]) -> Result<T> {
+    from_slice_with(bytes, &Config::default())
+}
+
+// load_slice() loads a value from the start of `bytes`, returning the number of bytes it took up.
+// `Limits::max_bytes` is applied by cutting the slice short, so nested loads must not apply it
+// again.
+fn load_slice<'de, T: LoadBorrowed<'de>>(bytes: &'de [u8], cfg: &Config) -> Result<(T, usize)> {
+    let (input, max) = match cfg.limits.max_bytes {
+        Some(max) if bytes.len() as u64 > max => (&bytes[..max as usize], Some(max)),
+        _ => (bytes, None),
+    };
+
+    let slice_cfg = Config {
+        limits: Limits {
+            max_bytes: None,
+            ..cfg.limits
+        },
+        state: LoadState {
+            tracked: true,
+            ..cfg.state
+        },
+        ..*cfg
+    };
+
+    let mut read = input;
+
+    match T::load_borrowed(&mut read, &slice_cfg) {
+        Ok(value) => Ok((value, input.len() - read.len())),
+        Err(err) => {
+            let err = match max {
+                // the value goes on past the limit
+                Some(max) if *err.kind() == UnexpectedEof => err.with_kind(LimitExceeded {
+                    limit: "max_bytes",
+                    max,
+                }),
+                _ => err,
+            };
+
+            Err(err.at_offset((input.len() - read.len()) as u64))
+        }
+    }
+}
+
+// from_slice_with() loads a value from the start of `bytes`, ignoring anything past its end.
+pub fn from_slice_with<'de, T: LoadBorrowed<'de>>(bytes: &'de [u8], cfg: &Config) -> Result<T> {
+    load_slice(bytes, cfg).map(|(value, _)| value)
+}
+
+pub fn from_slice_strict<'de, T: LoadBorrowed<'de>>(bytes: &'de [u8]) -> Result<T> {
+    from_slice_strict_with(bytes, &Config::default())
+}
+
+// from_slice_strict_with() loads a value from `bytes` in strict mode, failing with `TrailingBytes`
+// unless it takes up all of them.
+pub fn from_slice_strict_with<'de, T: LoadBorrowed<'de>>(
+    bytes: &'de [u8],
+    cfg: &Config,
+) -> Result<T> {
+    let (value, len) = load_slice(
+        bytes,
+        &Config {
+            strict: true,
+            ..*cfg
+        },
+    )?;
+
+    if len != bytes.len() {
+        return Err(Error::from(TrailingBytes).at_offset(len as u64));
+    }
+
+    Ok(value)
+}
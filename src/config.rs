@@ -0,0 +1,219 @@
+use std::fmt;
+
+use crate::err::{ErrorKind::LimitExceeded, Result};
+
+// Encoding used for `String`s.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StringEncoding {
+    // The raw bytes followed by a NUL byte; strings containing NUL bytes cannot be dumped.
+    NulTerminated,
+
+    // The length of the string, followed by its raw bytes.
+    LengthPrefixed,
+}
+
+// Byte order of fixed-width integers, floats, lengths and tags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+// Encoding used for integers wider than a byte, and for collection and string lengths.
+// Fixed lengths are always 64 bits wide.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IntEncoding {
+    // All the bytes of the type, in the byte order of the config.
+    Fixed,
+
+    // LEB128, with signed integers zigzag-encoded first so that small negative numbers stay short.
+    Varint,
+}
+
+// Encoding used for the tags of derived enums which do not specify their own
+// `#[load_dump(tag_repr = "...")]`. Dumping a tag that does not fit fails with `TagOverflow`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TagEncoding {
+    U8,
+    U16,
+    U32,
+    Varint,
+}
+
+// Limits guard `Load` against hostile input, which could otherwise make it allocate or read
+// without bound. Going over any of them fails with `LimitExceeded`; `None` means unlimited.
+// Only `max_depth` is set by default.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    // Maximum number of elements in a single collection. Collections of zero-sized types, whose
+    // elements take up no input, are held to `DEFAULT_MAX_ZERO_SIZED_LEN` when it is not set.
+    pub max_len: Option<u64>,
+
+    // Maximum number of bytes in a single string.
+    pub max_string: Option<u64>,
+
+    // Maximum number of bytes consumed by a single `ReadExt::load_with` or `from_slice_with` call.
+    pub max_bytes: Option<u64>,
+
+    // Maximum nesting depth of boxes, collections and derived types, which keeps crafted input
+    // for recursive types from overflowing the stack.
+    pub max_depth: Option<u32>,
+}
+
+// Nesting allowed by default, deep enough for any sensible value while keeping far from the end
+// of the stack of a thread.
+pub const DEFAULT_MAX_DEPTH: u32 = 128;
+
+// Elements allowed in a collection of zero-sized values when `max_len` is not set. Nothing else
+// bounds how long loading them takes, as a huge length takes up a few bytes of input.
+pub const DEFAULT_MAX_ZERO_SIZED_LEN: u64 = 1 << 20;
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_len: None,
+            max_string: None,
+            max_bytes: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
+
+// Config selects the wire format used by `Dump` and `Load`, and it is passed along to every
+// nested value. The default config gives the original encoding, which the `with_*` methods
+// change one setting at a time.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct Config {
+    pub endian: Endian,
+    pub ints: IntEncoding,
+    pub lengths: IntEncoding,
+    pub strings: StringEncoding,
+    pub tags: TagEncoding,
+
+    // Dump every NaN as the same quiet NaN, so that equal values always give equal bytes.
+    pub canonical_nan: bool,
+
+    // Dump the entries of `HashMap`s and `HashSet`s sorted by the bytes of their keys, so that
+    // equal collections always give equal bytes.
+    pub canonical: bool,
+
+    // Reject input that no value dumps into, such as booleans and `Option` flags other than 0
+    // and 1, or maps and sets with duplicate keys, instead of reading it leniently.
+    pub strict: bool,
+
+    pub limits: Limits,
+
+    // State of the load in progress, which only this crate can change.
+    pub(crate) state: LoadState,
+}
+
+// LoadState is carried along with the config by nested `Load` calls.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct LoadState {
+    // nesting depth of the value being loaded, increased by `Config::descend()`
+    pub(crate) depth: u32,
+
+    // the input is already wrapped by the `ReadExt::load_with` call that started the load
+    pub(crate) tracked: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            endian: Endian::Little,
+            ints: IntEncoding::Fixed,
+            lengths: IntEncoding::Fixed,
+            strings: StringEncoding::NulTerminated,
+            tags: TagEncoding::U32,
+            canonical_nan: false,
+            canonical: false,
+            strict: false,
+            limits: Limits::default(),
+            state: LoadState::default(),
+        }
+    }
+}
+
+// The state of the load in progress is left out, as it is not part of the format.
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("endian", &self.endian)
+            .field("ints", &self.ints)
+            .field("lengths", &self.lengths)
+            .field("strings", &self.strings)
+            .field("tags", &self.tags)
+            .field("canonical_nan", &self.canonical_nan)
+            .field("canonical", &self.canonical)
+            .field("strict", &self.strict)
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl Config {
+    pub fn with_endian(self, endian: Endian) -> Self {
+        Self { endian, ..self }
+    }
+
+    pub fn with_ints(self, ints: IntEncoding) -> Self {
+        Self { ints, ..self }
+    }
+
+    pub fn with_lengths(self, lengths: IntEncoding) -> Self {
+        Self { lengths, ..self }
+    }
+
+    pub fn with_strings(self, strings: StringEncoding) -> Self {
+        Self { strings, ..self }
+    }
+
+    pub fn with_tags(self, tags: TagEncoding) -> Self {
+        Self { tags, ..self }
+    }
+
+    pub fn with_canonical_nan(self, canonical_nan: bool) -> Self {
+        Self {
+            canonical_nan,
+            ..self
+        }
+    }
+
+    pub fn with_canonical(self, canonical: bool) -> Self {
+        Self { canonical, ..self }
+    }
+
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    pub fn with_limits(self, limits: Limits) -> Self {
+        Self { limits, ..self }
+    }
+
+    // descend() returns the config for loading a value nested one level deeper, failing with
+    // `LimitExceeded` past `limits.max_depth`.
+    pub fn descend(&self) -> Result<Self> {
+        let depth = self.state.depth + 1;
+
+        match self.limits.max_depth {
+            Some(max) if depth > max => Err(LimitExceeded {
+                limit: "max_depth",
+                max: u64::from(max),
+            }
+            .into()),
+            _ => Ok(Self {
+                state: LoadState {
+                    depth,
+                    ..self.state
+                },
+                ..*self
+            }),
+        }
+    }
+
+    // depth() is the nesting depth of the value being loaded with this config.
+    pub fn depth(&self) -> u32 {
+        self.state.depth
+    }
+}
@@ -1,58 +1,247 @@
+use std::error;
 use std::fmt;
-
-use failure::{Backtrace, Context, Fail};
+use std::io;
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
+type Source = Box<dyn error::Error + Send + Sync + 'static>;
+
+// Error is an `ErrorKind`, plus the error that caused it (e.g. an `io::Error`), if any.
+// Errors from `Load` also tell where the input could not be decoded.
 #[derive(Debug)]
 pub struct Error {
-    inner: Context<ErrorKind>,
+    kind: ErrorKind,
+    source: Option<Source>,
+
+    // bytes consumed by the failed load, from where it started
+    offset: Option<u64>,
+    // path to the value that failed to load, innermost step first
+    path: Vec<Step>,
 }
 
-impl Fail for Error {
-    fn cause(&self) -> Option<&Fail> {
-        self.inner.cause()
+#[derive(Debug)]
+enum Step {
+    Type(&'static str),
+    Field(&'static str),
+    Index(u64),
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    // offset() is the number of bytes that had been consumed by a failed load when it failed.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    // path() is the path to the value that failed to load, e.g. `Sample.arr[1].Struct.f2`,
+    // starting from the outermost derived type.
+    pub fn path(&self) -> Option<String> {
+        if self.path.is_empty() {
+            return None;
+        }
+
+        let mut path = String::new();
+
+        for (n, step) in self.path.iter().rev().enumerate() {
+            match *step {
+                // nested types are already named by the field holding them
+                Step::Type(name) if n == 0 => path.push_str(name),
+                Step::Type(..) => {}
+                Step::Field(name) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+
+                    path.push_str(name);
+                }
+                Step::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+
+        Some(path)
+    }
+
+    // in_field(), at_index() and in_type() add a step in front of the path of an error while it
+    // propagates out of a field (or enum variant), a collection element or a type.
+    pub fn in_field(mut self, name: &'static str) -> Self {
+        self.path.push(Step::Field(name));
+        self
+    }
+
+    pub fn at_index(mut self, index: u64) -> Self {
+        self.path.push(Step::Index(index));
+        self
+    }
+
+    pub fn in_type(mut self, name: &'static str) -> Self {
+        self.path.push(Step::Type(name));
+        self
+    }
+
+    pub(crate) fn at_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
     }
+}
+
+// load_in_type() runs `load`, the body of the `Load` impl of the type called `name`, adding the
+// type to the path of its errors.
+pub fn load_in_type<T>(name: &'static str, load: impl FnOnce() -> Result<T>) -> Result<T> {
+    load().map_err(|err| err.in_type(name))
+}
 
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.source {
+            Some(ref source) => Some(&**source),
+            None => None,
+        }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.inner, f)
+        fmt::Display::fmt(&self.kind, f)?;
+
+        match (self.path(), self.offset) {
+            (Some(path), Some(offset)) => write!(f, " ({}, byte {})", path, offset),
+            (Some(path), None) => write!(f, " ({})", path),
+            (None, Some(offset)) => write!(f, " (byte {})", offset),
+            (None, None) => Ok(()),
+        }
     }
 }
 
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Self {
         Self {
-            inner: Context::new(kind),
+            kind,
+            source: None,
+            offset: None,
+            path: Vec::new(),
         }
     }
 }
 
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Self {
-        Self { inner }
+// ResultExt turns the errors of the libraries used by this crate into an `Error` of the given
+// kind, keeping the original one as its source.
+pub(crate) trait ResultExt<T> {
+    fn context(self, kind: ErrorKind) -> Result<T>;
+}
+
+impl<T, E: error::Error + Send + Sync + 'static> ResultExt<T> for ::std::result::Result<T, E> {
+    fn context(self, kind: ErrorKind) -> Result<T> {
+        self.map_err(|err| Error {
+            source: Some(Box::new(err)),
+            ..Error::from(kind)
+        })
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+// ReadResultExt is `ResultExt` for reads from the input, which fail with `UnexpectedEof` instead
+// of `FileRead` if the input ends too early.
+pub(crate) trait ReadResultExt<T> {
+    fn read_context(self) -> Result<T>;
+}
+
+impl<T> ReadResultExt<T> for io::Result<T> {
+    fn read_context(self) -> Result<T> {
+        let kind = match self {
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+            _ => ErrorKind::FileRead,
+        };
+
+        self.context(kind)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
-    #[fail(display = "Cannot read from file")]
     FileRead,
-
-    #[fail(display = "Cannot write from file")]
     FileWrite,
-
-    #[fail(display = "Invalid UTF-8 detected in input")]
+    UnexpectedEof,
     InvalidUtf8,
-
-    #[fail(display = "No size hint")]
+    InteriorNul,
+    InvalidVarint,
+    InvalidChar {
+        value: u32,
+    },
+    InvalidBool {
+        value: u8,
+    },
+    IntOverflow,
+    UnexpectedZero,
+    TagOverflow {
+        value: u64,
+    },
+    InvalidDiscriminant {
+        type_name: &'static str,
+        value: u64,
+    },
+    NotSerializable {
+        type_name: &'static str,
+        variant: &'static str,
+        message: &'static str,
+    },
+    LimitExceeded {
+        limit: &'static str,
+        max: u64,
+    },
+    TrailingBytes,
+    DuplicateKey,
     NoSizeHint,
-
-    #[fail(display = "Unknown error")]
     Unknown,
 }
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ErrorKind::*;
+
+        match *self {
+            FileRead => write!(f, "Cannot read from file"),
+            FileWrite => write!(f, "Cannot write from file"),
+            UnexpectedEof => write!(f, "Input ends in the middle of a value"),
+            InvalidUtf8 => write!(f, "Invalid UTF-8 detected in input"),
+            InteriorNul => write!(f, "NUL byte inside a NUL-terminated string"),
+            InvalidVarint => write!(f, "Invalid variable-length integer"),
+            InvalidChar { value } => write!(f, "{:#x} is not a valid char", value),
+            InvalidBool { value } => write!(f, "{:#x} is not a valid bool", value),
+            IntOverflow => write!(f, "Integer does not fit in the target type"),
+            UnexpectedZero => write!(f, "Zero found where a non-zero integer was expected"),
+            TagOverflow { value } => write!(
+                f,
+                "Tag {} does not fit in the configured tag encoding",
+                value
+            ),
+            InvalidDiscriminant { type_name, value } => write!(
+                f,
+                "{} is out of the range of values of enum {}",
+                value, type_name
+            ),
+            NotSerializable {
+                type_name,
+                variant,
+                message,
+            } => write!(
+                f,
+                "{}::{} cannot be serialized: {}",
+                type_name, variant, message
+            ),
+            LimitExceeded { limit, max } => {
+                write!(f, "Input exceeds the {} limit of {}", limit, max)
+            }
+            TrailingBytes => write!(f, "Input continues past the end of the value"),
+            DuplicateKey => write!(f, "Key found twice in a map or set"),
+            NoSizeHint => write!(f, "No size hint"),
+            Unknown => write!(f, "Unknown error"),
+        }
+    }
+}
@@ -1,118 +1,241 @@
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
-use std::io;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Read};
 use std::marker::PhantomData;
-use std::mem::size_of;
+use std::mem::{size_of, size_of_val};
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
 
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use failure::ResultExt;
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt, BE, LE};
 use num_traits::{FromPrimitive, Signed, ToPrimitive, Unsigned};
 
+use crate::config::*;
 use crate::err::ErrorKind::*;
 use crate::err::*;
+use crate::size::{iter_size, ByteCounter};
 
 pub trait Dump: Sized {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()>;
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()>;
+
+    // dump_slice() dumps the elements of a slice, without its length. Primitive types override it
+    // to write the whole slice at once.
+    fn dump_slice(
+        items: &[Self],
+        write: &mut (impl io::Write + ?Sized),
+        cfg: &Config,
+    ) -> Result<()> {
+        items.iter().try_for_each(|item| item.dump(write, cfg))
+    }
+
+    // size_hint() returns the exact number of bytes `dump()` writes with `cfg`. Unless overridden,
+    // it dumps the value into a byte counter.
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        let mut counter = ByteCounter::default();
+
+        self.dump(&mut counter, cfg)?;
+
+        Ok(counter.bytes)
+    }
+
+    // slice_size_hint() is the size of the elements written by `dump_slice()`.
+    fn slice_size_hint(items: &[Self], cfg: &Config) -> Result<u64> {
+        items.iter().map(|item| item.size_hint(cfg)).sum()
+    }
 }
 
-impl<'a, T: Dump> Dump for &'a T {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        T::dump(*self, write)
+impl<T: Dump> Dump for &T {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        T::dump(*self, write, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        T::size_hint(*self, cfg)
     }
 }
 
-impl<'a, T: Dump> Dump for &'a mut T {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        T::dump(*self, write)
+impl<T: Dump> Dump for &mut T {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        T::dump(*self, write, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        T::size_hint(*self, cfg)
     }
 }
 
-impl<'a, T: Dump> Dump for Box<T> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.dump(self as &T)
+impl<T: Dump> Dump for Box<T> {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.dump_with(cfg, self as &T)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        T::size_hint(self, cfg)
     }
 }
 
 impl<T: Dump> Dump for Option<T> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
         match self {
             Some(val) => {
-                write.dump(&true)?;
-                write.dump(val)
+                write.dump_with(cfg, &true)?;
+                write.dump_with(cfg, val)
             }
-            None => write.dump(&false),
+            None => write.dump_with(cfg, &false),
+        }
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        match self {
+            Some(val) => Ok(true.size_hint(cfg)? + val.size_hint(cfg)?),
+            None => false.size_hint(cfg),
         }
     }
 }
 
 impl<T: Dump> Dump for Box<[T]> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.dump(&(self as &[T]))
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.dump_with(cfg, &(self as &[T]))
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        (self as &[T]).size_hint(cfg)
     }
 }
 
 impl<T: Dump, U: Dump> Dump for BTreeMap<T, U> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.write_iter(self.iter(), self.len())
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_iter(self.iter(), self.len(), cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        iter_size(self.iter(), self.len(), cfg)
     }
 }
 
 impl<T: Dump> Dump for BTreeSet<T> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.write_iter(self.iter(), self.len())
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_iter(self.iter(), self.len(), cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        iter_size(self.iter(), self.len(), cfg)
     }
 }
 
 impl<T: Dump + std::cmp::Eq + std::hash::Hash, U: Dump> Dump for HashMap<T, U> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.write_iter(self.iter(), self.len())
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        if cfg.canonical {
+            return write_sorted(write, self.iter(), cfg);
+        }
+
+        write.write_iter(self.iter(), self.len(), cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        iter_size(self.iter(), self.len(), cfg)
     }
 }
 
 impl<T: Dump + std::cmp::Eq + std::hash::Hash> Dump for HashSet<T> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.write_iter(self.iter(), self.len())
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        if cfg.canonical {
+            return write_sorted(write, self.iter().map(|key| (key, ())), cfg);
+        }
+
+        write.write_iter(self.iter(), self.len(), cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        iter_size(self.iter(), self.len(), cfg)
     }
 }
 
 impl<T: Dump> Dump for Vec<T> {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.dump(&(self as &[T]))
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.dump_with(cfg, &(self as &[T]))
     }
-}
 
-impl<'a, T: Dump> Dump for &'a [T] {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        /* write.dump(&(self.len() as u64))?;
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        (self as &[T]).size_hint(cfg)
+    }
+}
 
-        for elem in self.iter() {
-            write.dump(elem)?
-        }
+impl<T: Dump> Dump for &[T] {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_len(self.len(), cfg)?;
 
-        Ok(()) */
+        T::dump_slice(self, write, cfg)
+    }
 
-        write.write_iter(self.iter(), self.len())
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        Ok(cfg.len_size(self.len()) + T::slice_size_hint(self, cfg)?)
     }
 }
 
 impl Dump for bool {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.dump(&(*self as u8)) // should serialise true/false as 1/0
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.dump_with(cfg, &(*self as u8)) // should serialise true/false as 1/0
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        (*self as u8).size_hint(cfg)
     }
 }
 
 impl Dump for String {
-    fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-        write.write_all(self.as_bytes()).context(FileWrite)?;
-        write.write(&[0u8]).context(FileWrite)?;
-        Ok(())
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_str(self, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        cfg.str_size(self)
+    }
+}
+
+impl Dump for &str {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_str(self, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        cfg.str_size(self)
+    }
+}
+
+impl Dump for Cow<'_, str> {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.write_str(self, cfg)
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        cfg.str_size(self)
     }
 }
 
 macro_rules! dump_sint {
     ($ty:ty) => {
         impl Dump for $ty {
-            fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-                write.write_leint(*self)
+            fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+                write.write_signed(*self, cfg)
+            }
+
+            fn size_hint(&self, cfg: &Config) -> Result<u64> {
+                Ok(cfg.signed_size(*self))
+            }
+
+            fn slice_size_hint(items: &[Self], cfg: &Config) -> Result<u64> {
+                bulk_size(items, cfg)
+            }
+
+            fn dump_slice(
+                items: &[Self],
+                write: &mut (impl io::Write + ?Sized),
+                cfg: &Config,
+            ) -> Result<()> {
+                dump_bulk(items, write, cfg)
             }
         }
     };
@@ -121,8 +244,24 @@ macro_rules! dump_sint {
 macro_rules! dump_uint {
     ($ty:ty) => {
         impl Dump for $ty {
-            fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
-                write.write_leuint(*self)
+            fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+                write.write_unsigned(*self, cfg)
+            }
+
+            fn size_hint(&self, cfg: &Config) -> Result<u64> {
+                Ok(cfg.unsigned_size(*self))
+            }
+
+            fn slice_size_hint(items: &[Self], cfg: &Config) -> Result<u64> {
+                bulk_size(items, cfg)
+            }
+
+            fn dump_slice(
+                items: &[Self],
+                write: &mut (impl io::Write + ?Sized),
+                cfg: &Config,
+            ) -> Result<()> {
+                dump_bulk(items, write, cfg)
             }
         }
     };
@@ -140,67 +279,356 @@ dump_uint!(u32);
 dump_uint!(u64);
 dump_uint!(u128);
 
+// Floats are written as the fixed-width representation of their IEEE-754 bits, so that they
+// round-trip exactly (NaN payloads included).
+macro_rules! dump_float {
+    ($ty:ident, $bits:ty, $nan:expr) => {
+        impl Dump for $ty {
+            fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+                let bits: $bits = if cfg.canonical_nan && self.is_nan() {
+                    $nan
+                } else {
+                    self.to_bits()
+                };
+
+                write.write_fixed_uint(bits, cfg.endian)
+            }
+
+            fn size_hint(&self, _: &Config) -> Result<u64> {
+                Ok(size_of::<Self>() as u64)
+            }
+
+            fn slice_size_hint(items: &[Self], cfg: &Config) -> Result<u64> {
+                bulk_size(items, cfg)
+            }
+
+            fn dump_slice(
+                items: &[Self],
+                write: &mut (impl io::Write + ?Sized),
+                cfg: &Config,
+            ) -> Result<()> {
+                dump_bulk(items, write, cfg)
+            }
+        }
+    };
+}
+
+dump_float!(f32, u32, 0x7FC0_0000);
+dump_float!(f64, u64, 0x7FF8_0000_0000_0000);
+
+impl Dump for char {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.dump_with(cfg, &u32::from(*self))
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        u32::from(*self).size_hint(cfg)
+    }
+}
+
+// usize and isize are always 64 bits wide on the wire, regardless of the target.
+impl Dump for usize {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.dump_with(cfg, &(*self as u64))
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        (*self as u64).size_hint(cfg)
+    }
+}
+
+impl Dump for isize {
+    fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+        write.dump_with(cfg, &(*self as i64))
+    }
+
+    fn size_hint(&self, cfg: &Config) -> Result<u64> {
+        (*self as i64).size_hint(cfg)
+    }
+}
+
+macro_rules! dump_nonzero {
+    ($($ty:ident)+) => {
+        $(
+            impl Dump for $ty {
+                fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
+                    write.dump_with(cfg, &self.get())
+                }
+
+                fn size_hint(&self, cfg: &Config) -> Result<u64> {
+                    self.get().size_hint(cfg)
+                }
+            }
+        )*
+    };
+}
+
+dump_nonzero!(NonZeroI8 NonZeroI16 NonZeroI32 NonZeroI64 NonZeroI128 NonZeroIsize);
+dump_nonzero!(NonZeroU8 NonZeroU16 NonZeroU32 NonZeroU64 NonZeroU128 NonZeroUsize);
+
 pub trait WriteExt: WriteBytesExt {
     fn dump<D: Dump>(&mut self, to_dump: &D) -> Result<()> {
-        to_dump.dump(self)
+        self.dump_with(&Config::default(), to_dump)
+    }
+
+    fn dump_with<D: Dump>(&mut self, cfg: &Config, to_dump: &D) -> Result<()> {
+        to_dump.dump(self, cfg)
     }
 
-    fn write_iter<T: Dump>(&mut self, it: impl Iterator<Item=T>, len: usize) -> Result<()> {   
-        let sz_hint = it.size_hint().1.ok_or_else(|| NoSizeHint)?;
+    fn write_iter<T: Dump>(&mut self, it: impl Iterator<Item=T>, len: usize, cfg: &Config) -> Result<()> {   
+        let sz_hint = it.size_hint().1.ok_or(NoSizeHint)?;
 
         assert!(sz_hint == len);
 
-        self.dump(&(len as u64))?;
+        self.write_len(len, cfg)?;
 
         for elem in it {
-            self.dump(&elem)?
+            self.dump_with(cfg, &elem)?
         }
 
         Ok(())
     }
 
-    fn write_leint<N: Signed + ToPrimitive>(&mut self, n: N) -> Result<()> {
-        if size_of::<N>() == size_of::<i128>() {
-            self.write_i128::<LE>(n.to_i128().unwrap())
-                .context(FileWrite)?;
-        } else {
-            let prim = n.to_i64().unwrap();
+    // write_len() writes the length of a collection or of a length-prefixed string.
+    fn write_len(&mut self, len: usize, cfg: &Config) -> Result<()> {
+        match cfg.lengths {
+            IntEncoding::Fixed => self.write_fixed_uint(len as u64, cfg.endian),
+            IntEncoding::Varint => self.write_varint(len as u64),
+        }
+    }
+
+    // write_tag() writes the tag of a derived enum variant.
+    fn write_tag(&mut self, tag: u64, enc: TagEncoding, cfg: &Config) -> Result<()> {
+        let overflow = |_| Error::from(TagOverflow { value: tag });
+        let endian = cfg.endian;
+
+        match enc {
+            TagEncoding::U8 => self.write_leuint(u8::try_from(tag).map_err(overflow)?),
+            TagEncoding::U16 => {
+                self.write_fixed_uint(u16::try_from(tag).map_err(overflow)?, endian)
+            }
+            TagEncoding::U32 => {
+                self.write_fixed_uint(u32::try_from(tag).map_err(overflow)?, endian)
+            }
+            TagEncoding::Varint => self.write_varint(tag),
+        }
+    }
+
+    fn write_str(&mut self, s: &str, cfg: &Config) -> Result<()> {
+        match cfg.strings {
+            StringEncoding::NulTerminated => {
+                // the string would end at its first NUL when read back
+                if s.as_bytes().contains(&0u8) {
+                    return Err(InteriorNul.into());
+                }
+
+                self.write_all(s.as_bytes()).context(FileWrite)?;
+                self.write_u8(0u8).context(FileWrite)?;
+            }
 
-            self.write_int::<LE>(prim, size_of::<N>())
-                .context(FileWrite)?;
+            StringEncoding::LengthPrefixed => {
+                self.write_len(s.len(), cfg)?;
+                self.write_all(s.as_bytes()).context(FileWrite)?;
+            }
         }
 
         Ok(())
     }
 
-    fn write_leuint<N: Unsigned + ToPrimitive>(&mut self, n: N) -> Result<()> {
-        if size_of::<N>() == size_of::<u128>() {
-            self.write_u128::<LE>(n.to_u128().unwrap())
-                .context(FileWrite)?;
-        } else {
-            let prim = n.to_u64().unwrap();
+    // write_bytes() writes an opaque blob as its length followed by its contents, the same way as
+    // a `Vec<u8>`.
+    fn write_bytes(&mut self, bytes: &[u8], cfg: &Config) -> Result<()> {
+        self.write_len(bytes.len(), cfg)?;
 
-            self.write_uint::<LE>(prim, size_of::<N>())
-                .context(FileWrite)?;
-        }
+        self.write_all(bytes).context(FileWrite)?;
 
         Ok(())
     }
+
+    fn write_leint<N: Signed + ToPrimitive>(&mut self, n: N) -> Result<()> {
+        write_sint::<LE, _>(self, n)
+    }
+
+    fn write_beint<N: Signed + ToPrimitive>(&mut self, n: N) -> Result<()> {
+        write_sint::<BE, _>(self, n)
+    }
+
+    // write_fixed_int() and write_fixed_uint() write `n` using all the bytes of its type, in the
+    // given byte order.
+    fn write_fixed_int<N: Signed + ToPrimitive>(&mut self, n: N, endian: Endian) -> Result<()> {
+        match endian {
+            Endian::Little => self.write_leint(n),
+            Endian::Big => self.write_beint(n),
+        }
+    }
+
+    fn write_fixed_uint<N: Unsigned + ToPrimitive>(&mut self, n: N, endian: Endian) -> Result<()> {
+        match endian {
+            Endian::Little => self.write_leuint(n),
+            Endian::Big => self.write_beuint(n),
+        }
+    }
+
+    // write_varint() writes `n` as an unsigned LEB128 number, 7 bits per byte.
+    fn write_varint(&mut self, n: u64) -> Result<()> {
+        write_uvarint(self, n.into())
+    }
+
+    // write_signed() and write_unsigned() write `n` with the integer encoding selected by `cfg`.
+    // Single byte integers are always written as they are.
+    fn write_signed<N: Signed + ToPrimitive>(&mut self, n: N, cfg: &Config) -> Result<()> {
+        match cfg.ints {
+            IntEncoding::Varint if size_of::<N>() > 1 => {
+                // zigzag encoding maps 0, -1, 1, -2, ... to 0, 1, 2, 3, ...
+                let n = n.to_i128().unwrap();
+
+                write_uvarint(self, ((n << 1) ^ (n >> 127)) as u128)
+            }
+            _ => self.write_fixed_int(n, cfg.endian),
+        }
+    }
+
+    fn write_unsigned<N: Unsigned + ToPrimitive>(&mut self, n: N, cfg: &Config) -> Result<()> {
+        match cfg.ints {
+            IntEncoding::Varint if size_of::<N>() > 1 => write_uvarint(self, n.to_u128().unwrap()),
+            _ => self.write_fixed_uint(n, cfg.endian),
+        }
+    }
+
+    fn write_leuint<N: Unsigned + ToPrimitive>(&mut self, n: N) -> Result<()> {
+        write_uint::<LE, _>(self, n)
+    }
+
+    fn write_beuint<N: Unsigned + ToPrimitive>(&mut self, n: N) -> Result<()> {
+        write_uint::<BE, _>(self, n)
+    }
 }
 
 impl<W: io::Write + ?Sized> WriteExt for W {}
 
+fn write_sint<B: ByteOrder, N: Signed + ToPrimitive>(
+    write: &mut (impl io::Write + ?Sized),
+    n: N,
+) -> Result<()> {
+    if size_of::<N>() == size_of::<i128>() {
+        write
+            .write_i128::<B>(n.to_i128().unwrap())
+            .context(FileWrite)?;
+    } else {
+        let prim = n.to_i64().unwrap();
+
+        write
+            .write_int::<B>(prim, size_of::<N>())
+            .context(FileWrite)?;
+    }
+
+    Ok(())
+}
+
+fn write_uint<B: ByteOrder, N: Unsigned + ToPrimitive>(
+    write: &mut (impl io::Write + ?Sized),
+    n: N,
+) -> Result<()> {
+    if size_of::<N>() == size_of::<u128>() {
+        write
+            .write_u128::<B>(n.to_u128().unwrap())
+            .context(FileWrite)?;
+    } else {
+        let prim = n.to_u64().unwrap();
+
+        write
+            .write_uint::<B>(prim, size_of::<N>())
+            .context(FileWrite)?;
+    }
+
+    Ok(())
+}
+
+// write_sorted() writes the entries of a map (or set, with `()` values) like `write_iter`, sorted
+// by the bytes of their keys so that the order of iteration does not change the output.
+fn write_sorted<K: Dump, V: Dump>(
+    write: &mut (impl io::Write + ?Sized),
+    entries: impl Iterator<Item = (K, V)>,
+    cfg: &Config,
+) -> Result<()> {
+    let mut sorted = entries
+        .map(|(key, value)| {
+            let mut key_bytes = Vec::new();
+            key_bytes.dump_with(cfg, &key)?;
+
+            Ok((key_bytes, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    sorted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    write.write_len(sorted.len(), cfg)?;
+
+    for (key_bytes, value) in sorted {
+        write.write_all(&key_bytes).context(FileWrite)?;
+        write.dump_with(cfg, &value)?;
+    }
+
+    Ok(())
+}
+
+fn write_uvarint(write: &mut (impl io::Write + ?Sized), mut n: u128) -> Result<()> {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+
+        if n == 0 {
+            return write.write_u8(byte).context(FileWrite);
+        }
+
+        write.write_u8(byte | 0x80).context(FileWrite)?;
+    }
+}
+
+// read_uvarint() reads an unsigned LEB128 number of at most `bits` bits, rejecting encodings
+// that are longer than needed or that overflow.
+fn read_uvarint(read: &mut impl io::Read, bits: u32) -> Result<u128> {
+    let mut ret = 0u128;
+
+    for shift in (0..bits).step_by(7) {
+        let byte = read.read_u8().read_context()?;
+
+        // the last byte can only contribute the bits that are left
+        if bits - shift < 7 && u32::from(byte) >> (bits - shift) != 0 {
+            return Err(InvalidVarint.into());
+        }
+
+        ret |= u128::from(byte & 0x7F) << shift;
+
+        if byte & 0x80 == 0 {
+            // a trailing zero byte means the same value could be written in fewer bytes
+            if byte == 0 && shift > 0 {
+                return Err(InvalidVarint.into());
+            }
+
+            return Ok(ret);
+        }
+    }
+
+    // the continuation bit was set on the last possible byte
+    Err(InvalidVarint.into())
+}
+
 macro_rules! impl_dump_array_len {
     ($n:literal) => {
         impl<T: Dump> Dump for [T; $n] {
             #[allow(non_snake_case)]
-            fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
+            fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
                 for el in self {
-                    write.dump(el)?;
+                    write.dump_with(cfg, el)?;
                 }
 
                 Ok(())
             }
+
+            fn size_hint(&self, cfg: &Config) -> Result<u64> {
+                self.iter().map(|el| el.size_hint(cfg)).sum()
+            }
         }
     };
 }
@@ -216,21 +644,32 @@ impl_dump_array!(0 1 2 3 4 5 6 7 8 9 10 11 12);
 macro_rules! impl_dump_tuple {
     () => (
         impl Dump for () {
-            fn dump(&self, _: &mut (impl io::Write + ?Sized)) -> Result<()> {
+            fn dump(&self, _: &mut (impl io::Write + ?Sized), _: &Config) -> Result<()> {
                 Ok(())
             }
+
+            fn size_hint(&self, _: &Config) -> Result<u64> {
+                Ok(0)
+            }
         }
     );
 
     ( $($name:ident)+) => (
         impl<$($name: Dump),*> Dump for ($($name,)*) {
             #[allow(non_snake_case)]
-            fn dump(&self, write: &mut (impl io::Write + ?Sized)) -> Result<()> {
+            fn dump(&self, write: &mut (impl io::Write + ?Sized), cfg: &Config) -> Result<()> {
                 let ($(ref $name,)*) = *self;
-                $($name.dump(write)?;)*
+                $($name.dump(write, cfg)?;)*
 
                 Ok(())
             }
+
+            #[allow(non_snake_case)]
+            fn size_hint(&self, cfg: &Config) -> Result<u64> {
+                let ($(ref $name,)*) = *self;
+
+                Ok(0 $(+ $name.size_hint(cfg)?)*)
+            }
         }
     );
 }
@@ -249,39 +688,50 @@ impl_dump_tuple! { A B C D E F G H I J }
 impl_dump_tuple! { A B C D E F G H I J K }
 impl_dump_tuple! { A B C D E F G H I J K L }
 
+// Impls load their nested values with `Load::load`, passing the config along; `ReadExt::load_with`
+// is meant for top-level loads.
 pub trait Load: Sized {
-    fn load(read: &mut impl io::Read) -> Result<Self>;
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self>;
+
+    // load_array() loads the elements of an array, whose length has already been read. Primitive
+    // types override it to read many elements at once.
+    fn load_array<R: io::BufRead>(elems: ArrayIter<'_, Self, R>) -> Result<Vec<Self>> {
+        elems.collect()
+    }
 }
 
+// Also loads the flag of `Option`s.
 impl Load for bool {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
-        read.load::<u8>().map(|n| match n {
-            0 => false,
-            _ => true,
-        })
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        match u8::load(read, cfg)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value if cfg.strict => Err(InvalidBool { value }.into()),
+            _ => Ok(true),
+        }
     }
 }
 
 impl<T: Load> Load for Box<T> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
-        Ok(Box::new(read.load()?))
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        Ok(Box::new(T::load(read, &cfg.descend()?)?))
     }
 }
 
 impl<T: Load> Load for Box<[T]> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
-        Vec::load(read).map(Vec::into_boxed_slice)
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        Vec::load(read, cfg).map(Vec::into_boxed_slice)
     }
 }
 
 impl<T: Load + std::cmp::Ord, U: Load> Load for BTreeMap<T,U> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
         let mut ret = Self::new();
 
-        for pair_res in read.iter_array()? {
+        for (n, pair_res) in read.iter_array(cfg)?.enumerate() {
             let (key, val) = pair_res?;
             
-            ret.insert(key, val);
+            check_new_key(ret.insert(key, val).is_none(), n, cfg)?;
         }
 
         Ok(ret)
@@ -289,11 +739,11 @@ impl<T: Load + std::cmp::Ord, U: Load> Load for BTreeMap<T,U> {
 }
 
 impl<T: Load + std::cmp::Ord,> Load for BTreeSet<T> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
         let mut ret = Self::new();
 
-        for res in read.iter_array()? {
-            ret.insert(res?);
+        for (n, res) in read.iter_array(cfg)?.enumerate() {
+            check_new_key(ret.insert(res?), n, cfg)?;
         }
 
         Ok(ret)
@@ -301,13 +751,13 @@ impl<T: Load + std::cmp::Ord,> Load for BTreeSet<T> {
 }
 
 impl<T: Load + std::cmp::Eq + std::hash::Hash, U: Load> Load for HashMap<T,U> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
         let mut ret = Self::new();
 
-        for pair_res in read.iter_array()? {
+        for (n, pair_res) in read.iter_array(cfg)?.enumerate() {
             let (key, val) = pair_res?;
             
-            ret.insert(key, val);
+            check_new_key(ret.insert(key, val).is_none(), n, cfg)?;
         }
 
         Ok(ret)
@@ -315,21 +765,30 @@ impl<T: Load + std::cmp::Eq + std::hash::Hash, U: Load> Load for HashMap<T,U> {
 }
 
 impl<T: Load + std::cmp::Eq + std::hash::Hash> Load for HashSet<T> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
         let mut ret = Self::new();
 
-        for res in read.iter_array()? {
-            ret.insert(res?);
+        for (n, res) in read.iter_array(cfg)?.enumerate() {
+            check_new_key(ret.insert(res?), n, cfg)?;
         }
 
         Ok(ret)
     }
 }
 
+// check_new_key() fails in strict mode if the `n`-th element of a map or set repeats a key.
+pub(crate) fn check_new_key(new: bool, n: usize, cfg: &Config) -> Result<()> {
+    if !new && cfg.strict {
+        return Err(Error::from(DuplicateKey).at_index(n as u64));
+    }
+
+    Ok(())
+}
+
 impl<T: Load> Load for Option<T> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
-        if read.load()? {
-            read.load()
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        if bool::load(read, cfg)? {
+            T::load(read, cfg).map(Some)
         } else {
             Ok(None)
         }
@@ -337,43 +796,26 @@ impl<T: Load> Load for Option<T> {
 }
 
 impl Load for String {
-    // load() loads a null-terminated string.
-    fn load(read: &mut impl io::Read) -> Result<Self> {
-        let mut ret = Vec::new();
-
-        let mut buf = [0u8; 1];
-
-        loop {
-            read.read_exact(&mut buf).context(FileRead)?;
-
-            if buf[0] == 0u8 {
-                break;
-            }
-
-            ret.push(buf[0]);
-        }
-
-        Ok(Self::from_utf8(ret).context(InvalidUtf8)?)
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        read.read_string(cfg)
     }
 }
 
 impl<T: Load> Load for Vec<T> {
-    fn load(read: &mut impl io::Read) -> Result<Self> {
-        let mut ret = Self::new();
-
-        for next in read.iter_array()? {
-            ret.push(next?);
-        }
-
-        Ok(ret)
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        T::load_array(read.iter_array(cfg)?)
     }
 }
 
 macro_rules! load_sint {
     ($ty:ty) => {
         impl Load for $ty {
-            fn load(read: &mut impl io::Read) -> Result<Self> {
-                read.read_leint()
+            fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+                read.read_signed(cfg)
+            }
+
+            fn load_array<R: io::BufRead>(elems: ArrayIter<'_, Self, R>) -> Result<Vec<Self>> {
+                load_bulk(elems)
             }
         }
     };
@@ -382,8 +824,12 @@ macro_rules! load_sint {
 macro_rules! load_uint {
     ($ty:ty) => {
         impl Load for $ty {
-            fn load(read: &mut impl io::Read) -> Result<Self> {
-                read.read_leuint()
+            fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+                read.read_unsigned(cfg)
+            }
+
+            fn load_array<R: io::BufRead>(elems: ArrayIter<'_, Self, R>) -> Result<Vec<Self>> {
+                load_bulk(elems)
             }
         }
     };
@@ -401,8 +847,265 @@ load_uint!(u32);
 load_uint!(u64);
 load_uint!(u128);
 
+macro_rules! load_float {
+    ($ty:ident, $bits:ty) => {
+        impl Load for $ty {
+            fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+                read.read_fixed_uint::<$bits>(cfg.endian)
+                    .map(<$ty>::from_bits)
+            }
+
+            fn load_array<R: io::BufRead>(elems: ArrayIter<'_, Self, R>) -> Result<Vec<Self>> {
+                load_bulk(elems)
+            }
+        }
+    };
+}
+
+load_float!(f32, u32);
+load_float!(f64, u64);
+
+// Bulk is implemented by the primitive types whose slices can be dumped and loaded as a whole,
+// converting many elements at a time between their in-memory and wire representations.
+// It is only implemented, right below, for integers and floats, which have no padding and accept
+// any bit pattern, so that their slices can be viewed as bytes.
+trait Bulk: Dump + Load + Copy + Default {
+    // fixed() tells whether `cfg` encodes the type with a fixed width, which the bulk path needs.
+    fn fixed(cfg: &Config) -> bool {
+        cfg.ints == IntEncoding::Fixed || size_of::<Self>() == 1
+    }
+
+    // native() tells whether the fixed-width encoding of the type is the same as its in-memory
+    // representation, so that slices need no conversion at all.
+    fn native(cfg: &Config) -> bool {
+        cfg.endian == NATIVE_ENDIAN || size_of::<Self>() == 1
+    }
+
+    fn write_into(src: &[Self], dst: &mut [u8], endian: Endian);
+
+    fn read_into(read: &mut impl io::Read, dst: &mut [Self], endian: Endian) -> io::Result<()>;
+}
+
+macro_rules! impl_bulk {
+    ($ty:ty, $write_into:ident, $read_into:ident) => {
+        impl Bulk for $ty {
+            fn write_into(src: &[Self], dst: &mut [u8], endian: Endian) {
+                match endian {
+                    Endian::Little => LE::$write_into(src, dst),
+                    Endian::Big => BE::$write_into(src, dst),
+                }
+            }
+
+            fn read_into(
+                read: &mut impl io::Read,
+                dst: &mut [Self],
+                endian: Endian,
+            ) -> io::Result<()> {
+                match endian {
+                    Endian::Little => read.$read_into::<LE>(dst),
+                    Endian::Big => read.$read_into::<BE>(dst),
+                }
+            }
+        }
+    };
+}
+
+impl Bulk for u8 {
+    fn write_into(src: &[Self], dst: &mut [u8], _: Endian) {
+        dst.copy_from_slice(src);
+    }
+
+    fn read_into(read: &mut impl io::Read, dst: &mut [Self], _: Endian) -> io::Result<()> {
+        read.read_exact(dst)
+    }
+}
+
+impl Bulk for i8 {
+    fn write_into(src: &[Self], dst: &mut [u8], _: Endian) {
+        for (byte, &n) in dst.iter_mut().zip(src) {
+            *byte = n as u8;
+        }
+    }
+
+    fn read_into(read: &mut impl io::Read, dst: &mut [Self], _: Endian) -> io::Result<()> {
+        read.read_i8_into(dst)
+    }
+}
+
+impl_bulk!(i16, write_i16_into, read_i16_into);
+impl_bulk!(i32, write_i32_into, read_i32_into);
+impl_bulk!(i64, write_i64_into, read_i64_into);
+impl_bulk!(i128, write_i128_into, read_i128_into);
+
+impl_bulk!(u16, write_u16_into, read_u16_into);
+impl_bulk!(u32, write_u32_into, read_u32_into);
+impl_bulk!(u64, write_u64_into, read_u64_into);
+impl_bulk!(u128, write_u128_into, read_u128_into);
+
+macro_rules! impl_bulk_float {
+    ($ty:ty, $write_into:ident, $read_into:ident) => {
+        impl Bulk for $ty {
+            // canonical NaNs must be checked one by one
+            fn fixed(cfg: &Config) -> bool {
+                !cfg.canonical_nan
+            }
+
+            fn write_into(src: &[Self], dst: &mut [u8], endian: Endian) {
+                match endian {
+                    Endian::Little => LE::$write_into(src, dst),
+                    Endian::Big => BE::$write_into(src, dst),
+                }
+            }
+
+            fn read_into(
+                read: &mut impl io::Read,
+                dst: &mut [Self],
+                endian: Endian,
+            ) -> io::Result<()> {
+                match endian {
+                    Endian::Little => read.$read_into::<LE>(dst),
+                    Endian::Big => read.$read_into::<BE>(dst),
+                }
+            }
+        }
+    };
+}
+
+impl_bulk_float!(f32, write_f32_into, read_f32_into);
+impl_bulk_float!(f64, write_f64_into, read_f64_into);
+
+// Bulk conversions go through a buffer of at most this many bytes.
+const BULK_CHUNK: usize = 64 * 1024;
+
+const NATIVE_ENDIAN: Endian = if cfg!(target_endian = "big") {
+    Endian::Big
+} else {
+    Endian::Little
+};
+
+fn bulk_bytes<T: Bulk>(items: &[T]) -> &[u8] {
+    // SAFETY: `Bulk` types have no padding, so every byte of the slice is initialized
+    unsafe { std::slice::from_raw_parts(items.as_ptr() as *const u8, size_of_val(items)) }
+}
+
+fn bulk_bytes_mut<T: Bulk>(items: &mut [T]) -> &mut [u8] {
+    // SAFETY: as for `bulk_bytes()`, and any bytes written into it make valid `Bulk` values
+    unsafe { std::slice::from_raw_parts_mut(items.as_mut_ptr() as *mut u8, size_of_val(items)) }
+}
+
+fn dump_bulk<T: Bulk>(
+    items: &[T],
+    write: &mut (impl io::Write + ?Sized),
+    cfg: &Config,
+) -> Result<()> {
+    if !T::fixed(cfg) {
+        return items.iter().try_for_each(|item| item.dump(write, cfg));
+    }
+
+    if T::native(cfg) {
+        write.write_all(bulk_bytes(items)).context(FileWrite)?;
+
+        return Ok(());
+    }
+
+    let chunk_len = BULK_CHUNK / size_of::<T>();
+    let mut buf = vec![0u8; items.len().min(chunk_len) * size_of::<T>()];
+
+    for chunk in items.chunks(chunk_len) {
+        let bytes = &mut buf[..size_of_val(chunk)];
+
+        T::write_into(chunk, bytes, cfg.endian);
+        write.write_all(bytes).context(FileWrite)?;
+    }
+
+    Ok(())
+}
+
+fn bulk_size<T: Bulk>(items: &[T], cfg: &Config) -> Result<u64> {
+    if !T::fixed(cfg) {
+        return items.iter().map(|item| item.size_hint(cfg)).sum();
+    }
+
+    Ok(size_of_val(items) as u64)
+}
+
+fn load_bulk<T: Bulk, R: io::BufRead>(elems: ArrayIter<'_, T, R>) -> Result<Vec<T>> {
+    if !T::fixed(&elems.cfg) {
+        return elems.collect();
+    }
+
+    let chunk_len = (BULK_CHUNK / size_of::<T>()) as u64;
+    let mut ret = Vec::new();
+    let mut left = elems.n_elems;
+
+    // the length may be lying, so the vector only grows as the input turns out to hold the
+    // elements: each read is as long as all the previous ones, and a few reads load any slice
+    while left > 0 {
+        let start = ret.len();
+        let n = left.min(chunk_len.max(start as u64));
+
+        ret.resize(start + n as usize, T::default());
+
+        let dst = &mut ret[start..];
+        let res = if T::native(&elems.cfg) {
+            elems.read.read_exact(bulk_bytes_mut(dst))
+        } else {
+            T::read_into(elems.read, dst, elems.cfg.endian)
+        };
+
+        res.read_context()
+            .map_err(|err| err.at_index(start as u64))?;
+
+        left -= n;
+    }
+
+    Ok(ret)
+}
+
+impl Load for char {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        let value = u32::load(read, cfg)?;
+
+        // rejects surrogates and values past U+10FFFF
+        Self::from_u32(value).ok_or_else(|| InvalidChar { value }.into())
+    }
+}
+
+impl Load for usize {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        let value = u64::load(read, cfg)?;
+
+        Self::try_from(value).map_err(|_| IntOverflow.into())
+    }
+}
+
+impl Load for isize {
+    fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+        let value = i64::load(read, cfg)?;
+
+        Self::try_from(value).map_err(|_| IntOverflow.into())
+    }
+}
+
+macro_rules! load_nonzero {
+    ($($ty:ident)+) => {
+        $(
+            impl Load for $ty {
+                fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+                    Self::new(Load::load(read, cfg)?).ok_or_else(|| UnexpectedZero.into())
+                }
+            }
+        )*
+    };
+}
+
+load_nonzero!(NonZeroI8 NonZeroI16 NonZeroI32 NonZeroI64 NonZeroI128 NonZeroIsize);
+load_nonzero!(NonZeroU8 NonZeroU16 NonZeroU32 NonZeroU64 NonZeroU128 NonZeroUsize);
+
 pub struct ArrayIter<'a, T: Load, R: 'a> {
     read: &'a mut R,
+    // config of the elements, one level deeper than the collection
+    cfg: Config,
 
     n_elems: u64,
     read_elems: u64,
@@ -418,7 +1121,7 @@ pub struct ArrayIter<'a, T: Load, R: 'a> {
 impl<'a, T, R> Iterator for ArrayIter<'a, T, R>
 where
     T: Load,
-    R: 'a + io::Read,
+    R: 'a + io::BufRead,
 {
     type Item = Result<T>;
 
@@ -426,7 +1129,7 @@ where
     fn next(&mut self) -> Option<Result<T>> {
         if !self.failed && self.read_elems < self.n_elems {
             Some({
-                let res = self.read.load();
+                let res = T::load(self.read, &self.cfg);
 
                 // if ok, increment the iterator
                 // if not ok, mark it as failed
@@ -435,7 +1138,7 @@ where
                     Err(..) => self.failed = true,
                 };
 
-                res
+                res.map_err(|err| err.at_index(self.read_elems))
             })
         } else {
             None
@@ -443,20 +1146,75 @@ where
     }
 }
 
-pub trait ReadExt: ReadBytesExt + Sized {
+// ReadExt needs a buffered reader, so that strings can be scanned for their end without reading
+// one byte at a time. Wrap unbuffered readers such as `File` or `TcpStream` in an `io::BufReader`.
+pub trait ReadExt: io::BufRead + ReadBytesExt + Sized {
     fn load<N: Load>(&mut self) -> Result<N> {
-        N::load(self)
+        self.load_with(&Config::default())
     }
 
-    fn iter_array<N>(&mut self) -> Result<ArrayIter<N, Self>>
+    // load_with() tracks the bytes consumed by a top-level load, so that its errors carry the
+    // offset where decoding failed. Nested loads get a config marking the input as tracked, and
+    // read it as it is.
+    fn load_with<N: Load>(&mut self, cfg: &Config) -> Result<N> {
+        if cfg.state.tracked && cfg.limits.max_bytes.is_none() {
+            return N::load(self, cfg);
+        }
+
+        load_tracked(self, cfg).map(|(value, _)| value)
+    }
+
+    fn load_exact<N: Load>(&mut self) -> Result<N> {
+        self.load_exact_with(&Config::default())
+    }
+
+    // load_exact_with() loads a value in strict mode, failing with `TrailingBytes` if the input
+    // continues past its end.
+    fn load_exact_with<N: Load>(&mut self, cfg: &Config) -> Result<N> {
+        let (value, pos) = load_tracked(
+            self,
+            &Config {
+                strict: true,
+                ..*cfg
+            },
+        )?;
+
+        if !self.fill_buf().read_context()?.is_empty() {
+            return Err(Error::from(TrailingBytes).at_offset(pos));
+        }
+
+        Ok(value)
+    }
+
+    fn try_load<N: Load>(&mut self) -> Result<Option<N>> {
+        self.try_load_with(&Config::default())
+    }
+
+    // try_load_with() loads the next value of a stream of values, returning `None` if the input
+    // ends right before it; an input ending in the middle of the value still fails, with
+    // `UnexpectedEof`.
+    fn try_load_with<N: Load>(&mut self, cfg: &Config) -> Result<Option<N>> {
+        if self.fill_buf().read_context()?.is_empty() {
+            return Ok(None);
+        }
+
+        self.load_with(cfg).map(Some)
+    }
+
+    fn iter_array<N>(&mut self, cfg: &Config) -> Result<ArrayIter<'_, N, Self>>
     where
         N: Load,
     {
         // read number of elements
-        let n_elems: u64 = self.load()?;
+        let n_elems = self.read_len(cfg)?;
+
+        check_len::<N>(n_elems, &cfg.limits)?;
+
+        let cfg = cfg.descend()?;
 
         Ok(ArrayIter {
             read: self,
+            cfg,
             n_elems,
             read_elems: 0,
             failed: false,
@@ -464,44 +1222,307 @@ pub trait ReadExt: ReadBytesExt + Sized {
         })
     }
 
+    // read_len() reads the length of a collection or of a length-prefixed string.
+    fn read_len(&mut self, cfg: &Config) -> Result<u64> {
+        match cfg.lengths {
+            IntEncoding::Fixed => self.read_fixed_uint(cfg.endian),
+            IntEncoding::Varint => self.read_varint(),
+        }
+    }
+
+    fn read_tag(&mut self, enc: TagEncoding, cfg: &Config) -> Result<u64> {
+        match enc {
+            TagEncoding::U8 => self.read_leuint::<u8>().map(u64::from),
+            TagEncoding::U16 => self.read_fixed_uint::<u16>(cfg.endian).map(u64::from),
+            TagEncoding::U32 => self.read_fixed_uint::<u32>(cfg.endian).map(u64::from),
+            TagEncoding::Varint => self.read_varint(),
+        }
+    }
+
+    fn read_string(&mut self, cfg: &Config) -> Result<String> {
+        let mut ret = Vec::new();
+
+        match cfg.strings {
+            StringEncoding::NulTerminated => {
+                let max = cfg.limits.max_string.unwrap_or(u64::MAX);
+
+                // one byte past the limit, so that a string of exactly `max` bytes still fits
+                self.by_ref()
+                    .take(max.saturating_add(1))
+                    .read_until(0u8, &mut ret)
+                    .read_context()?;
+
+                if ret.last() != Some(&0u8) {
+                    check_limit(ret.len() as u64, Some(max), "max_string")?;
+
+                    // the input ended before the NUL
+                    return Err(UnexpectedEof.into());
+                }
+
+                ret.pop();
+            }
+
+            StringEncoding::LengthPrefixed => {
+                let len = self.read_len(cfg)?;
+
+                check_limit(len, cfg.limits.max_string, "max_string")?;
+
+                ret = read_sized(self, len)?;
+            }
+        }
+
+        String::from_utf8(ret).context(InvalidUtf8)
+    }
+
+    // read_bytes() reads a blob written by `write_bytes()`.
+    fn read_bytes(&mut self, cfg: &Config) -> Result<Vec<u8>> {
+        let len = self.read_len(cfg)?;
+
+        check_limit(len, cfg.limits.max_len, "max_len")?;
+
+        read_sized(self, len)
+    }
+
     fn read_leint<N: Signed + FromPrimitive>(&mut self) -> Result<N> {
-        if size_of::<N>() == size_of::<i128>() {
-            let res = self.read_i128::<LE>().context(FileRead)?;
+        read_sint::<LE, _>(self)
+    }
 
-            Ok(<N>::from_i128(res).unwrap())
-        } else {
-            let res = self.read_int::<LE>(size_of::<N>()).context(FileRead)?;
+    fn read_beint<N: Signed + FromPrimitive>(&mut self) -> Result<N> {
+        read_sint::<BE, _>(self)
+    }
+
+    fn read_fixed_int<N: Signed + FromPrimitive>(&mut self, endian: Endian) -> Result<N> {
+        match endian {
+            Endian::Little => self.read_leint(),
+            Endian::Big => self.read_beint(),
+        }
+    }
 
-            Ok(<N>::from_i64(res).unwrap())
+    fn read_fixed_uint<N: Unsigned + FromPrimitive>(&mut self, endian: Endian) -> Result<N> {
+        match endian {
+            Endian::Little => self.read_leuint(),
+            Endian::Big => self.read_beuint(),
+        }
+    }
+
+    // read_varint() reads an unsigned LEB128 number, rejecting encodings that are longer than
+    // needed or that do not fit into an u64.
+    fn read_varint(&mut self) -> Result<u64> {
+        read_uvarint(self, 64).map(|n| n as u64)
+    }
+
+    // read_signed() and read_unsigned() are the counterparts of WriteExt::write_signed() and
+    // WriteExt::write_unsigned().
+    fn read_signed<N: Signed + FromPrimitive>(&mut self, cfg: &Config) -> Result<N> {
+        match cfg.ints {
+            IntEncoding::Varint if size_of::<N>() > 1 => {
+                let n = read_uvarint(self, 8 * size_of::<N>() as u32)?;
+                let n = (n >> 1) as i128 ^ -((n & 1) as i128);
+
+                Ok(<N>::from_i128(n).unwrap())
+            }
+            _ => self.read_fixed_int(cfg.endian),
+        }
+    }
+
+    fn read_unsigned<N: Unsigned + FromPrimitive>(&mut self, cfg: &Config) -> Result<N> {
+        match cfg.ints {
+            IntEncoding::Varint if size_of::<N>() > 1 => {
+                let n = read_uvarint(self, 8 * size_of::<N>() as u32)?;
+
+                Ok(<N>::from_u128(n).unwrap())
+            }
+            _ => self.read_fixed_uint(cfg.endian),
         }
     }
 
     fn read_leuint<N: Unsigned + FromPrimitive>(&mut self) -> Result<N> {
-        if size_of::<N>() == size_of::<u128>() {
-            let res = self.read_u128::<LE>().context(FileRead)?;
+        read_uint::<LE, _>(self)
+    }
 
-            Ok(<N>::from_u128(res).unwrap())
-        } else {
-            let res = self.read_uint::<LE>(size_of::<N>()).context(FileRead)?;
+    fn read_beuint<N: Unsigned + FromPrimitive>(&mut self) -> Result<N> {
+        read_uint::<BE, _>(self)
+    }
+}
+
+impl<R: io::BufRead> ReadExt for R {}
+
+// read_sized() reads exactly `len` bytes, with a single read unless they are more than
+// `BULK_CHUNK`.
+fn read_sized(read: &mut impl io::Read, len: u64) -> Result<Vec<u8>> {
+    let mut ret = Vec::new();
+    let mut left = len;
 
-            Ok(<N>::from_u64(res).unwrap())
+    // don't trust len for preallocation, the input may be lying
+    while left > 0 {
+        let start = ret.len();
+        let n = left.min(BULK_CHUNK as u64);
+
+        ret.resize(start + n as usize, 0u8);
+        read.read_exact(&mut ret[start..]).read_context()?;
+
+        left -= n;
+    }
+
+    Ok(ret)
+}
+
+pub(crate) fn check_limit(value: u64, max: Option<u64>, limit: &'static str) -> Result<()> {
+    match max {
+        Some(max) if value > max => Err(LimitExceeded { limit, max }.into()),
+        _ => Ok(()),
+    }
+}
+
+// check_len() checks the length of a collection of `T`s against `max_len`.
+pub(crate) fn check_len<T>(len: u64, limits: &Limits) -> Result<()> {
+    let max = match limits.max_len {
+        None if size_of::<T>() == 0 => Some(DEFAULT_MAX_ZERO_SIZED_LEN),
+        max => max,
+    };
+
+    check_limit(len, max, "max_len")
+}
+
+// load_tracked() loads a value through a `Tracker`, returning the number of bytes it took up.
+fn load_tracked<N: Load>(read: &mut impl io::BufRead, cfg: &Config) -> Result<(N, u64)> {
+    // the limit covers the whole value, so nested loads must not count it again
+    let inner_cfg = Config {
+        limits: Limits {
+            max_bytes: None,
+            ..cfg.limits
+        },
+        state: LoadState {
+            tracked: true,
+            ..cfg.state
+        },
+        ..*cfg
+    };
+
+    let mut tracker = Tracker {
+        read,
+        pos: 0,
+        max: cfg.limits.max_bytes,
+        exceeded: false,
+    };
+
+    match N::load(&mut tracker, &inner_cfg) {
+        Ok(value) => Ok((value, tracker.pos)),
+        Err(err) => {
+            let err = match cfg.limits.max_bytes {
+                Some(max) if tracker.exceeded => err.with_kind(LimitExceeded {
+                    limit: "max_bytes",
+                    max,
+                }),
+                _ => err,
+            };
+
+            // an outer tracker, if any, replaces it with its own offset
+            Err(err.at_offset(tracker.pos))
+        }
+    }
+}
+
+// Tracker counts the bytes consumed from the inner reader, failing every read past the first
+// `max` ones and marking itself as exceeded. Loads nested in a tracked one read their input as it
+// is, and impls of `Load` call `Load::load` rather than `ReadExt::load_with` for nested values, so
+// that a recursive type does not instantiate an endless chain of trackers.
+struct Tracker<R> {
+    read: R,
+    pos: u64,
+    max: Option<u64>,
+    exceeded: bool,
+}
+
+impl<R> Tracker<R> {
+    fn left(&self) -> u64 {
+        self.max.map_or(u64::MAX, |max| max - self.pos)
+    }
+}
+
+impl<R: io::BufRead> io::Read for Tracker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.left() == 0 {
+            self.exceeded = true;
+
+            return Err(io::Error::other("byte limit exceeded"));
         }
+
+        let max = (buf.len() as u64).min(self.left()) as usize;
+        let n = self.read.read(&mut buf[..max])?;
+
+        self.pos += n as u64;
+
+        Ok(n)
     }
 }
 
-impl<R: io::Read> ReadExt for R {}
+impl<R: io::BufRead> io::BufRead for Tracker<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let left = self.left();
+
+        if left == 0 {
+            // only fail if there is something past the limit, the input may end right there
+            if self.read.fill_buf()?.is_empty() {
+                return Ok(&[]);
+            }
+
+            self.exceeded = true;
+
+            return Err(io::Error::other("byte limit exceeded"));
+        }
+
+        let buf = self.read.fill_buf()?;
+        let max = (buf.len() as u64).min(left) as usize;
+
+        Ok(&buf[..max])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+        self.read.consume(amt);
+    }
+}
+
+fn read_sint<B: ByteOrder, N: Signed + FromPrimitive>(read: &mut impl io::Read) -> Result<N> {
+    if size_of::<N>() == size_of::<i128>() {
+        let res = read.read_i128::<B>().read_context()?;
+
+        Ok(<N>::from_i128(res).unwrap())
+    } else {
+        let res = read.read_int::<B>(size_of::<N>()).read_context()?;
+
+        Ok(<N>::from_i64(res).unwrap())
+    }
+}
+
+fn read_uint<B: ByteOrder, N: Unsigned + FromPrimitive>(read: &mut impl io::Read) -> Result<N> {
+    if size_of::<N>() == size_of::<u128>() {
+        let res = read.read_u128::<B>().read_context()?;
+
+        Ok(<N>::from_u128(res).unwrap())
+    } else {
+        let res = read.read_uint::<B>(size_of::<N>()).read_context()?;
+
+        Ok(<N>::from_u64(res).unwrap())
+    }
+}
 
 macro_rules! impl_load_array_len {
     ($n:literal) => {
         impl<T: Load> Load for [T; $n] {
-            #[allow(non_snake_case)]
-            fn load(read: &mut impl io::Read) -> Result<Self> {
+            #[allow(non_snake_case, clippy::reversed_empty_ranges)]
+            fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
                 use arrayvec::ArrayVec;
 
                 let mut arrv = ArrayVec::new();
 
-                for _ in 0..$n {
-                    arrv.push(read.load()?);
+                for i in 0..$n {
+                    arrv.push(T::load(read, cfg).map_err(|err| err.at_index(i))?);
                 }
 
                 arrv.into_inner().map_err(|_| ErrorKind::Unknown.into())
@@ -521,7 +1542,7 @@ impl_load_array!(0 1 2 3 4 5 6 7 8 9 10 11 12);
 macro_rules! impl_load_tuple {
     () => (
         impl Load for () {
-            fn load(_: &mut impl io::Read) -> Result<Self> {
+            fn load(_: &mut impl io::BufRead, _: &Config) -> Result<Self> {
                 Ok(())
             }
         }
@@ -530,8 +1551,8 @@ macro_rules! impl_load_tuple {
     ($($name:ident)+) => (
         impl<$($name: Load),*> Load for ($($name,)*) {
             #[allow(non_snake_case)]
-            fn load(read: &mut impl io::Read) -> Result<Self> {
-                $(let $name = read.load()?;)*
+            fn load(read: &mut impl io::BufRead, cfg: &Config) -> Result<Self> {
+                $(let $name = $name::load(read, cfg)?;)*
                 Ok(($($name,)*))
             }
         }
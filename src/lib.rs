@@ -1,25 +1,44 @@
-// Until extern crate self does not get into stable (1.34) this crate won't be able to run
-// its tests on stable (given that it cannot reference itself as a crate).
-//extern crate self as proc_dump_sample;
+// Lets the code generated by load-dump-derive refer to this crate as `::proc_macro_sample`
+// from inside its own tests.
+extern crate self as proc_macro_sample;
 
 extern crate arrayvec;
+#[cfg(feature = "bytes")]
+extern crate bytes;
 extern crate byteorder;
 extern crate enum_primitive;
-extern crate failure;
 extern crate num_traits;
 
-extern crate failure_derive;
 extern crate load_dump_derive;
 
+mod blob;
+
+mod borrow;
+
+mod config;
+
 mod ext_io;
 
+mod size;
+
 mod err;
 
+pub use blob::Blob;
+pub use borrow::{
+    from_slice, from_slice_strict, from_slice_strict_with, from_slice_with, LoadBorrowed,
+};
+pub use config::{
+    Config, Endian, IntEncoding, Limits, StringEncoding, TagEncoding, DEFAULT_MAX_DEPTH,
+    DEFAULT_MAX_ZERO_SIZED_LEN,
+};
 pub use err::*;
 pub use ext_io::{Dump, Load, ReadExt, WriteExt};
+pub use size::{serialized_size, serialized_size_with};
 
 #[cfg(test)]
 extern crate rand;
 
 #[cfg(test)]
+// test_dump_load borrows its buffer explicitly, as an example of dumping to any `io::Write`
+#[allow(clippy::needless_borrow)]
 mod tests;
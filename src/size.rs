@@ -0,0 +1,119 @@
+use std::io;
+use std::mem::size_of;
+
+use num_traits::{Signed, ToPrimitive, Unsigned};
+
+use crate::config::*;
+use crate::err::ErrorKind::*;
+use crate::err::*;
+use crate::ext_io::Dump;
+
+// Sizes of the building blocks of the encoding selected by a config, for `Dump::size_hint`.
+impl Config {
+    // len_size() is the size of the length of a collection or of a length-prefixed string.
+    pub fn len_size(&self, len: usize) -> u64 {
+        match self.lengths {
+            IntEncoding::Fixed => size_of::<u64>() as u64,
+            IntEncoding::Varint => uvarint_size(len as u128),
+        }
+    }
+
+    // tag_size() is the size of a derived enum tag, failing like `WriteExt::write_tag` does.
+    pub fn tag_size(&self, tag: u64, enc: TagEncoding) -> Result<u64> {
+        let (size, max) = match enc {
+            TagEncoding::U8 => (1, u64::from(u8::MAX)),
+            TagEncoding::U16 => (2, u64::from(u16::MAX)),
+            TagEncoding::U32 => (4, u64::from(u32::MAX)),
+            TagEncoding::Varint => (uvarint_size(tag.into()), u64::MAX),
+        };
+
+        if tag > max {
+            return Err(TagOverflow { value: tag }.into());
+        }
+
+        Ok(size)
+    }
+
+    pub fn str_size(&self, s: &str) -> Result<u64> {
+        match self.strings {
+            StringEncoding::NulTerminated => {
+                if s.as_bytes().contains(&0u8) {
+                    return Err(InteriorNul.into());
+                }
+
+                Ok(s.len() as u64 + 1)
+            }
+
+            StringEncoding::LengthPrefixed => Ok(self.len_size(s.len()) + s.len() as u64),
+        }
+    }
+
+    // bytes_size() is the size of a blob of `len` bytes.
+    pub fn bytes_size(&self, len: usize) -> u64 {
+        self.len_size(len) + len as u64
+    }
+
+    pub(crate) fn signed_size<N: Signed + ToPrimitive>(&self, n: N) -> u64 {
+        match self.ints {
+            IntEncoding::Varint if size_of::<N>() > 1 => {
+                let n = n.to_i128().unwrap();
+
+                uvarint_size(((n << 1) ^ (n >> 127)) as u128)
+            }
+            _ => size_of::<N>() as u64,
+        }
+    }
+
+    pub(crate) fn unsigned_size<N: Unsigned + ToPrimitive>(&self, n: N) -> u64 {
+        match self.ints {
+            IntEncoding::Varint if size_of::<N>() > 1 => uvarint_size(n.to_u128().unwrap()),
+            _ => size_of::<N>() as u64,
+        }
+    }
+}
+
+// uvarint_size() is the number of 7-bit groups needed by `n`.
+fn uvarint_size(n: u128) -> u64 {
+    let bits = 128 - u64::from(n.leading_zeros());
+
+    bits.div_ceil(7).max(1)
+}
+
+// iter_size() is the size of a collection written by `WriteExt::write_iter`.
+pub(crate) fn iter_size<T: Dump>(
+    it: impl Iterator<Item = T>,
+    len: usize,
+    cfg: &Config,
+) -> Result<u64> {
+    it.map(|item| item.size_hint(cfg))
+        .sum::<Result<u64>>()
+        .map(|size| cfg.len_size(len) + size)
+}
+
+// ByteCounter discards everything written to it, only keeping count of the bytes.
+#[derive(Default)]
+pub(crate) struct ByteCounter {
+    pub bytes: u64,
+}
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes += buf.len() as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn serialized_size<T: Dump>(value: &T) -> Result<u64> {
+    serialized_size_with(value, &Config::default())
+}
+
+// serialized_size_with() returns the number of bytes `value` takes when dumped with `cfg`,
+// without dumping it.
+pub fn serialized_size_with<T: Dump>(value: &T, cfg: &Config) -> Result<u64> {
+    value.size_hint(cfg)
+}
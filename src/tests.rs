@@ -1,7 +1,19 @@
-use std::collections::{BTreeMap, HashMap};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{self, BufReader};
 
 use rand::{thread_rng, Rng};
 
+use crate::config::{
+    Config, Endian, IntEncoding, Limits, StringEncoding, TagEncoding, DEFAULT_MAX_DEPTH,
+    DEFAULT_MAX_ZERO_SIZED_LEN,
+};
+use crate::blob::Blob;
+use crate::borrow::{
+    from_slice, from_slice_strict, from_slice_strict_with, from_slice_with, LoadBorrowed,
+};
+use crate::err::ErrorKind;
+use crate::size::{serialized_size, serialized_size_with};
 use crate::ext_io::{ReadExt, WriteExt};
 use load_dump_derive::*;
 
@@ -66,3 +78,1772 @@ fn test_dump_load() {
     // The reconstructed instance and the original one will be the same
     assert!(og_inst == dbg!(retrieved_inst));
 }
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+struct Wrapper<T> {
+    inner: Vec<T>,
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+// Opaque implements neither Dump nor Load, which `Partial` must not require of the type of its
+// skipped field.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Opaque;
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+struct Partial<T, S> {
+    val: T,
+    #[load_dump(skip)]
+    state: S,
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+#[load_dump(bound = "T: crate::Dump + crate::Load")]
+struct Bounded<T> {
+    inner: Box<T>,
+}
+
+fn round_trip<T: crate::Dump + crate::Load>(val: &T) -> T {
+    let mut buf = Vec::new();
+
+    buf.dump(val).unwrap();
+
+    (&buf[..]).load().unwrap()
+}
+
+#[test]
+fn test_generics() {
+    let wrapper = Wrapper {
+        inner: vec![1u16, 2, 3],
+    };
+    assert_eq!(wrapper, round_trip(&wrapper));
+
+    let left: Either<i8, String> = Either::Left(-3);
+    let right: Either<i8, String> = Either::Right("right".into());
+    assert_eq!(left, round_trip(&left));
+    assert_eq!(right, round_trip(&right));
+
+    let partial = Partial {
+        val: 42u64,
+        state: Opaque,
+    };
+    assert_eq!(partial, round_trip(&partial));
+
+    let bounded = Bounded {
+        inner: Box::new(7i32),
+    };
+    assert_eq!(bounded, round_trip(&bounded));
+}
+
+#[test]
+fn test_invalid_discriminant() {
+    let buf = [3u8, 0, 0, 0];
+
+    let err = (&buf[..]).load::<SampleEnum>().unwrap_err();
+
+    assert_eq!(
+        *err.kind(),
+        ErrorKind::InvalidDiscriminant {
+            type_name: "SampleEnum",
+            value: 3
+        }
+    );
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+enum Connection {
+    Closed,
+    #[load_dump(never = "live connections are transient")]
+    Open(u32),
+    #[load_dump(never = "handshakes are transient", panic)]
+    Handshake,
+    #[load_dump(never)]
+    Pending,
+    #[load_dump(panic, never)]
+    Dropped,
+}
+
+#[test]
+fn test_never() {
+    let mut buf = Vec::new();
+
+    let err = buf.dump(&Connection::Open(4)).unwrap_err();
+    let not_serializable = ErrorKind::NotSerializable {
+        type_name: "Connection",
+        variant: "Open",
+        message: "live connections are transient",
+    };
+
+    assert_eq!(*err.kind(), not_serializable);
+
+    // a `never` tag can still be found in a (malformed) input
+    let err = (&[1u8, 0, 0, 0][..]).load::<Connection>().unwrap_err();
+
+    assert_eq!(*err.kind(), not_serializable);
+
+    buf.clear();
+    buf.dump(&Connection::Closed).unwrap();
+    assert_eq!(Connection::Closed, (&buf[..]).load().unwrap());
+
+    // `never` without a message
+    let err = buf.dump(&Connection::Pending).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::NotSerializable {
+            variant: "Pending",
+            ..
+        }
+    ));
+
+    // sizing a `panic` variant fails instead
+    let err = serialized_size(&Connection::Handshake).unwrap_err();
+    assert_eq!(
+        *err.kind(),
+        ErrorKind::NotSerializable {
+            type_name: "Connection",
+            variant: "Handshake",
+            message: "handshakes are transient",
+        }
+    );
+}
+
+#[test]
+#[should_panic(expected = "Connection::Handshake cannot be dumped: handshakes are transient")]
+fn test_never_panic() {
+    let _ = Vec::new().dump(&Connection::Handshake);
+}
+
+#[test]
+#[should_panic(expected = "Connection::Dropped cannot be dumped")]
+fn test_never_panic_first() {
+    let _ = Vec::new().dump(&Connection::Dropped);
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+enum Tagged {
+    #[load_dump(tag = 7)]
+    Seven,
+    Eight(u8),
+    #[load_dump(tag = 2)]
+    Two { val: u8 },
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+enum Discriminants {
+    Ten = 10,
+    Eleven,
+    Twenty = 20,
+}
+
+#[test]
+fn test_tags() {
+    let cases = [
+        (Tagged::Seven, vec![7u8, 0, 0, 0]),
+        (Tagged::Eight(1), vec![8, 0, 0, 0, 1]),
+        (Tagged::Two { val: 3 }, vec![2, 0, 0, 0, 3]),
+    ];
+
+    for (val, bytes) in cases.iter() {
+        let mut buf = Vec::new();
+        buf.dump(val).unwrap();
+
+        assert_eq!(*bytes, buf);
+        assert_eq!(*val, (&buf[..]).load().unwrap());
+    }
+
+    let cases = [
+        (Discriminants::Ten, 10u8),
+        (Discriminants::Eleven, 11),
+        (Discriminants::Twenty, 20),
+    ];
+
+    for (val, tag) in cases.iter() {
+        let mut buf = Vec::new();
+        buf.dump(val).unwrap();
+
+        assert_eq!(vec![*tag, 0, 0, 0], buf);
+        assert_eq!(*val, (&buf[..]).load().unwrap());
+    }
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+#[load_dump(tag_repr = "u8")]
+enum SmallTag {
+    A,
+    B(u16),
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+#[load_dump(tag_repr = "varint")]
+enum VarTag {
+    Small,
+    #[load_dump(tag = 300)]
+    Large(u8),
+}
+
+#[test]
+fn test_tag_repr() {
+    let mut buf = Vec::new();
+    buf.dump(&SmallTag::B(1)).unwrap();
+
+    assert_eq!(vec![1u8, 1, 0], buf);
+    assert_eq!(SmallTag::B(1), (&buf[..]).load().unwrap());
+
+    buf.clear();
+    buf.dump(&VarTag::Large(9)).unwrap();
+
+    assert_eq!(vec![0xACu8, 0x02, 9], buf);
+    assert_eq!(VarTag::Large(9), (&buf[..]).load().unwrap());
+
+    buf.clear();
+    buf.dump(&VarTag::Small).unwrap();
+
+    assert_eq!(vec![0u8], buf);
+    assert_eq!(VarTag::Small, (&buf[..]).load().unwrap());
+}
+
+#[test]
+fn test_varint() {
+    for &n in [0u64, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX].iter() {
+        let mut buf = Vec::new();
+        buf.write_varint(n).unwrap();
+
+        assert_eq!(n, (&buf[..]).read_varint().unwrap());
+    }
+
+    // overlong encoding of 0
+    let err = (&[0x80u8, 0x00][..]).read_varint().unwrap_err();
+    assert_eq!(ErrorKind::InvalidVarint, *err.kind());
+
+    // 2^64 does not fit
+    let mut buf = vec![0xFFu8; 9];
+    buf.push(0x02);
+    let err = (&buf[..]).read_varint().unwrap_err();
+    assert_eq!(ErrorKind::InvalidVarint, *err.kind());
+}
+
+macro_rules! assert_round_trip {
+    ($($val:expr),+ $(,)*) => {
+        $(
+            let val = $val;
+            assert_eq!(val, round_trip(&val), "{}", stringify!($val));
+        )+
+    };
+}
+
+#[test]
+fn test_round_trip_ints() {
+    assert_round_trip!(
+        i8::MIN, i8::MAX, 0i8,
+        i16::MIN, i16::MAX, -1i16,
+        i32::MIN, i32::MAX, -1i32,
+        i64::MIN, i64::MAX, -1i64,
+        i128::MIN, i128::MAX, -1i128,
+        u8::MIN, u8::MAX,
+        u16::MIN, u16::MAX,
+        u32::MIN, u32::MAX,
+        u64::MIN, u64::MAX,
+        u128::MIN, u128::MAX,
+        true, false,
+    );
+}
+
+#[test]
+fn test_round_trip_std() {
+    assert_round_trip!(
+        String::new(),
+        "àèìòù".to_owned(),
+        Some(42u32),
+        None::<u32>,
+        Some(Some("nested".to_owned())),
+        Some(None::<u8>),
+        Box::new(-7i16),
+        Box::new(Box::new(3u8)),
+        Vec::<u64>::new().into_boxed_slice(),
+        vec![1i32, -2, 3].into_boxed_slice(),
+        Vec::<String>::new(),
+        vec![vec![1u8, 2], vec![], vec![3]],
+        vec![Some(1u8), None],
+        BTreeMap::<u8, u8>::new(),
+        (0..10).map(|n| (n, vec![n; n as usize])).collect::<BTreeMap<u8, Vec<u8>>>(),
+        (0..10i64).collect::<BTreeSet<_>>(),
+        HashMap::<String, u8>::new(),
+        (0..10).map(|n| (n.to_string(), n)).collect::<HashMap<String, u32>>(),
+        (0..10u16).collect::<HashSet<_>>(),
+    );
+}
+
+#[test]
+fn test_round_trip_arrays_tuples() {
+    assert_round_trip!(
+        [0u8; 0],
+        [5u8],
+        [1u16, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        [vec![1u8], vec![]],
+        (),
+        (1u8,),
+        (1u8, -1i64, "two".to_owned()),
+        (1u8, 2u16, 3u32, 4u64, 5u128, 6i8, 7i16, 8i32, 9i64, 10i128, true, ()),
+        ((1u8, (2u8,)), [(3u8, 4u8)]),
+    );
+}
+
+#[test]
+fn test_dump_refs() {
+    let owned = vec![1u32, 2, 3];
+
+    let mut expected = Vec::new();
+    expected.dump(&owned).unwrap();
+
+    let mut buf = Vec::new();
+    buf.dump(&&owned).unwrap();
+    assert_eq!(expected, buf);
+
+    buf.clear();
+    buf.dump(&&owned[..]).unwrap();
+    assert_eq!(expected, buf);
+
+    let mut owned = owned;
+
+    buf.clear();
+    buf.dump(&&mut owned).unwrap();
+    assert_eq!(expected, buf);
+}
+
+#[test]
+fn test_string_encoding() {
+    let cfg = Config {
+        strings: StringEncoding::LengthPrefixed,
+        ..Config::default()
+    };
+
+    let with_nul = "before\0after".to_owned();
+
+    let mut buf = Vec::new();
+    buf.dump_with(&cfg, &with_nul).unwrap();
+
+    assert_eq!(&[12u8, 0, 0, 0, 0, 0, 0, 0][..], &buf[..8]);
+    assert_eq!(with_nul, (&buf[..]).load_with::<String>(&cfg).unwrap());
+
+    // the legacy encoding cannot represent the string
+    buf.clear();
+    let err = buf.dump(&with_nul).unwrap_err();
+    assert_eq!(ErrorKind::InteriorNul, *err.kind());
+
+    buf.clear();
+    buf.dump(&"legacy".to_owned()).unwrap();
+    assert_eq!(b"legacy\0", &buf[..]);
+
+    // a truncated length-prefixed string
+    let err = (&[4u8, 0, 0, 0, 0, 0, 0, 0, b'a'][..])
+        .load_with::<String>(&cfg)
+        .unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+}
+
+#[test]
+fn test_floats() {
+    fn assert_bits_f32(val: f32) {
+        assert_eq!(val.to_bits(), round_trip(&val).to_bits());
+    }
+
+    fn assert_bits_f64(val: f64) {
+        assert_eq!(val.to_bits(), round_trip(&val).to_bits());
+    }
+
+    for &val in [
+        0.0,
+        -0.0,
+        1.5,
+        f32::MIN,
+        f32::MAX,
+        f32::MIN_POSITIVE,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+    ]
+    .iter()
+    {
+        assert_bits_f32(val);
+    }
+
+    for &val in [
+        0.0,
+        -0.0,
+        1.5,
+        f64::MIN,
+        f64::MAX,
+        f64::MIN_POSITIVE,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ]
+    .iter()
+    {
+        assert_bits_f64(val);
+    }
+
+    // NaNs with a payload and the sign bit set
+    assert_bits_f32(f32::from_bits(0xFFC0_1234));
+    assert_bits_f64(f64::from_bits(0xFFF8_0000_DEAD_BEEF));
+
+    let mut buf = Vec::new();
+    buf.dump(&1.0f64).unwrap();
+    assert_eq!(1.0f64.to_bits().to_le_bytes(), &buf[..]);
+
+    let cfg = Config {
+        canonical_nan: true,
+        ..Config::default()
+    };
+
+    let mut canonical = Vec::new();
+    canonical.dump_with(&cfg, &f64::NAN).unwrap();
+
+    buf.clear();
+    buf.dump_with(&cfg, &f64::from_bits(0xFFF8_0000_DEAD_BEEF))
+        .unwrap();
+    assert_eq!(canonical, buf);
+
+    buf.clear();
+    buf.dump_with(&cfg, &f32::from_bits(0xFFC0_1234)).unwrap();
+    assert_eq!(0x7FC0_0000u32.to_le_bytes(), &buf[..]);
+}
+
+#[test]
+fn test_more_primitives() {
+    use std::num::{NonZeroI64, NonZeroU32, NonZeroU8, NonZeroUsize};
+
+    assert_round_trip!(
+        'a',
+        '\0',
+        '\u{10FFFF}',
+        'è',
+        usize::MIN,
+        usize::MAX,
+        isize::MIN,
+        isize::MAX,
+        NonZeroU8::new(u8::MAX).unwrap(),
+        NonZeroU32::new(1).unwrap(),
+        NonZeroI64::new(i64::MIN).unwrap(),
+        NonZeroUsize::new(7).unwrap(),
+        Some(NonZeroU32::new(3).unwrap()),
+    );
+
+    // usize is always 64 bits wide
+    let mut buf = Vec::new();
+    buf.dump(&1usize).unwrap();
+    assert_eq!(&[1u8, 0, 0, 0, 0, 0, 0, 0], &buf[..]);
+
+    let mut buf = Vec::new();
+
+    // a surrogate and a value past U+10FFFF
+    for &value in [0xD800u32, 0x11_0000].iter() {
+        buf.clear();
+        buf.dump(&value).unwrap();
+
+        let err = (&buf[..]).load::<char>().unwrap_err();
+        assert_eq!(ErrorKind::InvalidChar { value }, *err.kind());
+    }
+
+    buf.clear();
+    buf.dump(&0u32).unwrap();
+    let err = (&buf[..]).load::<NonZeroU32>().unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedZero, *err.kind());
+
+    if cfg!(target_pointer_width = "32") {
+        buf.clear();
+        buf.dump(&u64::MAX).unwrap();
+        let err = (&buf[..]).load::<usize>().unwrap_err();
+        assert_eq!(ErrorKind::IntOverflow, *err.kind());
+    }
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+struct Ids {
+    #[load_dump(varint)]
+    small: Vec<u32>,
+    fixed: u32,
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Cache;
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+enum Delta {
+    Move {
+        #[load_dump(varint)]
+        dx: i64,
+        #[load_dump(skip)]
+        cache: Cache,
+    },
+    Jump(#[load_dump(skip)] Cache, #[load_dump(varint)] i32),
+}
+
+#[test]
+fn test_varint_ints() {
+    let cfg = Config {
+        ints: IntEncoding::Varint,
+        lengths: IntEncoding::Varint,
+        ..Config::default()
+    };
+
+    let cases: &[(i64, &[u8])] = &[
+        (0, &[0]),
+        (-1, &[1]),
+        (1, &[2]),
+        (-64, &[0x7F]),
+        (64, &[0x80, 0x01]),
+    ];
+
+    for &(val, bytes) in cases {
+        let mut buf = Vec::new();
+        buf.dump_with(&cfg, &val).unwrap();
+
+        assert_eq!(bytes, &buf[..]);
+        assert_eq!(val, (&buf[..]).load_with::<i64>(&cfg).unwrap());
+    }
+
+    for &val in [i64::MIN, i64::MAX].iter() {
+        let mut buf = Vec::new();
+        buf.dump_with(&cfg, &val).unwrap();
+
+        assert_eq!(10, buf.len());
+        assert_eq!(val, (&buf[..]).load_with::<i64>(&cfg).unwrap());
+    }
+
+    for &val in [i128::MIN, i128::MAX, 0].iter() {
+        let mut buf = Vec::new();
+        buf.dump_with(&cfg, &val).unwrap();
+
+        assert_eq!(val, (&buf[..]).load_with::<i128>(&cfg).unwrap());
+    }
+
+    // 2^16 does not fit in an u16
+    let err = (&[0x80u8, 0x80, 0x04][..])
+        .load_with::<u16>(&cfg)
+        .unwrap_err();
+    assert_eq!(ErrorKind::InvalidVarint, *err.kind());
+
+    // lengths are varints too
+    let mut buf = Vec::new();
+    buf.dump_with(&cfg, &vec![1u16, 300]).unwrap();
+    assert_eq!(&[2u8, 1, 0xAC, 0x02], &buf[..]);
+
+    let sample = Sample::get();
+
+    buf.clear();
+    buf.dump_with(&cfg, &sample).unwrap();
+    assert_eq!(sample, (&buf[..]).load_with(&cfg).unwrap());
+}
+
+#[test]
+fn test_varint_fields() {
+    let ids = Ids {
+        small: vec![1, 2],
+        fixed: 3,
+    };
+
+    let mut buf = Vec::new();
+    buf.dump(&ids).unwrap();
+
+    assert_eq!(&[2u8, 1, 2, 3, 0, 0, 0], &buf[..]);
+    assert_eq!(ids, (&buf[..]).load().unwrap());
+
+    let moves = [
+        Delta::Move {
+            dx: -2,
+            cache: Cache,
+        },
+        Delta::Jump(Cache, 100),
+    ];
+
+    buf.clear();
+    buf.dump(&moves).unwrap();
+
+    assert_eq!(&[0u8, 0, 0, 0, 3, 1, 0, 0, 0, 0xC8, 0x01], &buf[..]);
+    assert_eq!(moves, (&buf[..]).load::<[Delta; 2]>().unwrap());
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+enum Wide {
+    Small,
+    #[load_dump(tag = 1000)]
+    Large,
+}
+
+#[test]
+fn test_config_tags() {
+    let cfg = Config {
+        tags: TagEncoding::U8,
+        ..Config::default()
+    };
+
+    let mut buf = Vec::new();
+    buf.dump_with(&cfg, &Wide::Small).unwrap();
+    assert_eq!(&[0u8], &buf[..]);
+    assert_eq!(Wide::Small, (&buf[..]).load_with(&cfg).unwrap());
+
+    buf.clear();
+    let err = buf.dump_with(&cfg, &Wide::Large).unwrap_err();
+    assert_eq!(ErrorKind::TagOverflow { value: 1000 }, *err.kind());
+
+    // explicit tag representations ignore the config
+    buf.clear();
+    buf.dump_with(&cfg, &VarTag::Large(1)).unwrap();
+    assert_eq!(&[0xACu8, 0x02, 1], &buf[..]);
+
+    let cfg = Config {
+        tags: TagEncoding::Varint,
+        ..Config::default()
+    };
+
+    buf.clear();
+    buf.dump_with(&cfg, &Wide::Large).unwrap();
+    assert_eq!(&[0xE8u8, 0x07], &buf[..]);
+    assert_eq!(Wide::Large, (&buf[..]).load_with(&cfg).unwrap());
+}
+
+#[derive(Debug, Dump, Load, PartialEq)]
+struct Header {
+    #[load_dump(endian = "big")]
+    magic: u32,
+    #[load_dump(endian = "little")]
+    version: u16,
+    payload: Vec<u16>,
+}
+
+#[test]
+fn test_endian() {
+    let cfg = Config {
+        endian: Endian::Big,
+        ..Config::default()
+    };
+
+    let mut buf = Vec::new();
+    buf.dump_with(&cfg, &0x0102_0304u32).unwrap();
+    assert_eq!(&[1u8, 2, 3, 4], &buf[..]);
+    assert_eq!(0x0102_0304, (&buf[..]).load_with::<u32>(&cfg).unwrap());
+
+    buf.clear();
+    buf.dump_with(&cfg, &-2i16).unwrap();
+    assert_eq!(&[0xFFu8, 0xFE], &buf[..]);
+    assert_eq!(-2, (&buf[..]).load_with::<i16>(&cfg).unwrap());
+
+    buf.clear();
+    buf.dump_with(&cfg, &1.0f32).unwrap();
+    assert_eq!(&[0x3Fu8, 0x80, 0, 0], &buf[..]);
+    assert_eq!(1.0, (&buf[..]).load_with::<f32>(&cfg).unwrap());
+
+    // lengths and tags follow the config too
+    buf.clear();
+    buf.dump_with(&cfg, &vec![1u8]).unwrap();
+    assert_eq!(&[0u8, 0, 0, 0, 0, 0, 0, 1, 1], &buf[..]);
+
+    buf.clear();
+    buf.dump_with(&cfg, &Tagged::Eight(1)).unwrap();
+    assert_eq!(&[0u8, 0, 0, 8, 1], &buf[..]);
+    assert_eq!(Tagged::Eight(1), (&buf[..]).load_with(&cfg).unwrap());
+
+    // varints have no byte order
+    let var_cfg = Config {
+        ints: IntEncoding::Varint,
+        ..cfg
+    };
+
+    buf.clear();
+    buf.dump_with(&var_cfg, &300u32).unwrap();
+    assert_eq!(&[0xACu8, 0x02], &buf[..]);
+
+    for &val in [i128::MIN, -1, i128::MAX].iter() {
+        buf.clear();
+        buf.dump_with(&cfg, &val).unwrap();
+        assert_eq!(val.to_be_bytes(), &buf[..]);
+        assert_eq!(val, (&buf[..]).load_with::<i128>(&cfg).unwrap());
+    }
+
+    let sample = Sample::get();
+
+    buf.clear();
+    buf.dump_with(&cfg, &sample).unwrap();
+    assert_eq!(sample, (&buf[..]).load_with(&cfg).unwrap());
+}
+
+#[test]
+fn test_endian_fields() {
+    let header = Header {
+        magic: 0xCAFE_BABE,
+        version: 2,
+        payload: vec![0x0102],
+    };
+
+    let mut buf = Vec::new();
+    buf.dump(&header).unwrap();
+
+    assert_eq!(
+        &[0xCAu8, 0xFE, 0xBA, 0xBE, 2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 1],
+        &buf[..]
+    );
+    assert_eq!(header, (&buf[..]).load().unwrap());
+
+    // per-field attributes win over the config
+    let cfg = Config {
+        endian: Endian::Big,
+        ..Config::default()
+    };
+
+    buf.clear();
+    buf.dump_with(&cfg, &header).unwrap();
+
+    assert_eq!(
+        &[0xCAu8, 0xFE, 0xBA, 0xBE, 2, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2],
+        &buf[..]
+    );
+    assert_eq!(header, (&buf[..]).load_with(&cfg).unwrap());
+}
+
+#[test]
+fn test_limits() {
+    let limit = |limit, max| ErrorKind::LimitExceeded { limit, max };
+
+    let cfg = Config {
+        limits: Limits {
+            max_len: Some(2),
+            ..Limits::default()
+        },
+        ..Config::default()
+    };
+
+    let mut buf = Vec::new();
+    buf.dump(&vec![1u8, 2]).unwrap();
+    assert_eq!(vec![1u8, 2], (&buf[..]).load_with::<Vec<u8>>(&cfg).unwrap());
+
+    // the element count is checked before reading any element
+    let huge = [0xFFu8; 8];
+    let err = (&huge[..]).load_with::<Vec<u8>>(&cfg).unwrap_err();
+    assert_eq!(limit("max_len", 2), *err.kind());
+
+    let err = (&huge[..])
+        .load_with::<HashMap<u8, u8>>(&cfg)
+        .unwrap_err();
+    assert_eq!(limit("max_len", 2), *err.kind());
+
+    // zero-sized elements take up no input, so their count is limited even without max_len
+    let zero_sized = limit("max_len", DEFAULT_MAX_ZERO_SIZED_LEN);
+
+    let err = (&huge[..]).load::<Vec<()>>().unwrap_err();
+    assert_eq!(zero_sized, *err.kind());
+
+    let err = from_slice::<Vec<()>>(&huge).unwrap_err();
+    assert_eq!(zero_sized, *err.kind());
+
+    buf.clear();
+    buf.dump(&vec![(); 3]).unwrap();
+    assert_eq!(vec![(); 3], from_slice::<Vec<()>>(&buf).unwrap());
+
+    let cfg = Config {
+        limits: Limits {
+            max_string: Some(3),
+            ..Limits::default()
+        },
+        ..Config::default()
+    };
+
+    assert_eq!("abc", (&b"abc\0"[..]).load_with::<String>(&cfg).unwrap());
+
+    let err = (&b"abcd\0"[..]).load_with::<String>(&cfg).unwrap_err();
+    assert_eq!(limit("max_string", 3), *err.kind());
+
+    let cfg = Config {
+        strings: StringEncoding::LengthPrefixed,
+        ..cfg
+    };
+
+    let err = (&huge[..]).load_with::<String>(&cfg).unwrap_err();
+    assert_eq!(limit("max_string", 3), *err.kind());
+
+    let cfg = Config {
+        limits: Limits {
+            max_bytes: Some(12),
+            ..Limits::default()
+        },
+        ..Config::default()
+    };
+
+    buf.clear();
+    buf.dump(&vec![1u16, 2]).unwrap();
+    assert_eq!(vec![1u16, 2], (&buf[..]).load_with::<Vec<u16>>(&cfg).unwrap());
+
+    buf.clear();
+    buf.dump(&vec![1u16, 2, 3]).unwrap();
+    let err = (&buf[..]).load_with::<Vec<u16>>(&cfg).unwrap_err();
+    assert_eq!(limit("max_bytes", 12), *err.kind());
+
+    // a stream of NUL-less bytes is cut off as well
+    let mut endless = BufReader::new(io::Read::take(io::repeat(b'a'), 1 << 20));
+    let err = endless.load_with::<String>(&cfg).unwrap_err();
+    assert_eq!(limit("max_bytes", 12), *err.kind());
+
+    // the limit covers each top-level value on its own
+    buf.clear();
+    buf.dump(&vec![1u16, 2]).unwrap();
+    buf.dump(&vec![3u16, 4]).unwrap();
+
+    let mut read = &buf[..];
+    assert_eq!(vec![1u16, 2], read.load_with::<Vec<u16>>(&cfg).unwrap());
+    assert_eq!(vec![3u16, 4], read.load_with::<Vec<u16>>(&cfg).unwrap());
+
+    let sample = Sample::get();
+    buf.clear();
+    buf.dump(&sample).unwrap();
+
+    let cfg = Config {
+        limits: Limits {
+            max_len: Some(64),
+            max_string: Some(64),
+            max_bytes: Some(buf.len() as u64),
+            max_depth: Some(8),
+        },
+        ..Config::default()
+    };
+
+    assert_eq!(sample, (&buf[..]).load_with(&cfg).unwrap());
+}
+
+#[test]
+fn test_max_depth() {
+    fn nested(levels: usize) -> SampleEnum {
+        (0..levels).fold(SampleEnum::One, |inner, _| {
+            SampleEnum::Tuple(0, false, Box::new(inner))
+        })
+    }
+
+    let depth_cfg = |max_depth| Config {
+        limits: Limits {
+            max_depth: Some(max_depth),
+            ..Limits::default()
+        },
+        ..Config::default()
+    };
+
+    // every level counts both the enum and the box holding the next one
+    let mut buf = Vec::new();
+    buf.dump(&nested(10)).unwrap();
+    assert_eq!(nested(10), (&buf[..]).load_with(&depth_cfg(21)).unwrap());
+
+    let err = (&buf[..])
+        .load_with::<SampleEnum>(&depth_cfg(20))
+        .unwrap_err();
+    assert_eq!(
+        ErrorKind::LimitExceeded {
+            limit: "max_depth",
+            max: 20
+        },
+        *err.kind()
+    );
+
+    // collections count as a level as well
+    let err = (&[1u8, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 7][..])
+        .load_with::<Vec<Vec<u8>>>(&depth_cfg(1))
+        .unwrap_err();
+    assert_eq!(
+        ErrorKind::LimitExceeded {
+            limit: "max_depth",
+            max: 1
+        },
+        *err.kind()
+    );
+
+    // a crafted stream of endlessly nested tuples is rejected without overflowing the stack
+    let mut level = vec![2u8, 0, 0, 0];
+    level.extend_from_slice(&[0; 17]);
+    let crafted = level.repeat(100_000);
+
+    let err = (&crafted[..])
+        .load_with::<SampleEnum>(&depth_cfg(64))
+        .unwrap_err();
+    assert_eq!(
+        ErrorKind::LimitExceeded {
+            limit: "max_depth",
+            max: 64
+        },
+        *err.kind()
+    );
+
+    // the default config is limited too
+    let err = (&crafted[..]).load::<SampleEnum>().unwrap_err();
+    assert_eq!(
+        ErrorKind::LimitExceeded {
+            limit: "max_depth",
+            max: u64::from(DEFAULT_MAX_DEPTH)
+        },
+        *err.kind()
+    );
+
+    buf.clear();
+    buf.dump(&nested(63)).unwrap();
+    assert_eq!(nested(63), (&buf[..]).load().unwrap());
+
+    // the depth is not part of the format shown by the config
+    let cfg = Config::default().descend().unwrap();
+    assert_eq!(1, cfg.depth());
+    assert_eq!(format!("{:?}", Config::default()), format!("{:?}", cfg));
+}
+
+#[derive(Debug, Dump, LoadBorrowed, PartialEq)]
+struct Record<'a> {
+    id: u32,
+    name: &'a str,
+    payload: &'a [u8],
+    note: Cow<'a, str>,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Dump, LoadBorrowed, PartialEq)]
+enum Entry<'a, T> {
+    Named(&'a str, T),
+    Nested { inner: Record<'a> },
+}
+
+#[test]
+fn test_load_borrowed() {
+    let record = Record {
+        id: 7,
+        name: "seven",
+        payload: &[1, 2, 3],
+        note: Cow::Owned("owned".into()),
+        tags: vec!["a".into(), "b".into()],
+    };
+
+    let mut buf = Vec::new();
+    buf.dump(&record).unwrap();
+
+    let loaded: Record = from_slice(&buf).unwrap();
+    assert_eq!(record, loaded);
+
+    // borrowed fields point into the input
+    let range = buf.as_ptr_range();
+    assert!(range.contains(&loaded.name.as_ptr()));
+    assert!(range.contains(&loaded.payload.as_ptr()));
+    assert!(matches!(loaded.note, Cow::Borrowed(..)));
+
+    // owned types go through their `Load` impl, and share the encoding
+    assert_eq!(record.tags, from_slice::<Vec<String>>(&buf[buf.len() - 12..]).unwrap());
+    assert_eq!("seven", from_slice::<String>(&buf[4..]).unwrap());
+
+    let cfg = Config {
+        strings: StringEncoding::LengthPrefixed,
+        ints: IntEncoding::Varint,
+        lengths: IntEncoding::Varint,
+        ..Config::default()
+    };
+
+    let entries = vec![
+        Entry::Named("x", 1.5f64),
+        Entry::Nested {
+            inner: Record { tags: vec![], ..record },
+        },
+    ];
+
+    buf.clear();
+    buf.dump_with(&cfg, &entries).unwrap();
+
+    // a cursor loads one value after the other
+    let mut read = &buf[..];
+    let len = read.read_len(&cfg).unwrap();
+    assert_eq!(2, len);
+
+    for entry in entries.iter() {
+        assert_eq!(*entry, Entry::load_borrowed(&mut read, &cfg).unwrap());
+    }
+
+    assert!(read.is_empty());
+
+    let err = from_slice::<&str>(b"no nul").unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+
+    let err = from_slice::<&str>(b"\xFF\0").unwrap_err();
+    assert_eq!(ErrorKind::InvalidUtf8, *err.kind());
+
+    let err = from_slice_with::<&[u8]>(&[9, 1, 2], &cfg).unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+
+    // max_bytes covers the whole slice, and is not applied again to the owned values nested in it
+    let tags = vec!["abcdefgh".to_owned(), "ijkl".to_owned()];
+
+    buf.clear();
+    buf.dump(&tags).unwrap();
+
+    let max_bytes = |max| Config {
+        limits: Limits {
+            max_bytes: Some(max),
+            ..Limits::default()
+        },
+        ..Config::default()
+    };
+
+    let cfg = max_bytes(buf.len() as u64);
+    assert_eq!(tags, from_slice_with::<Vec<String>>(&buf, &cfg).unwrap());
+    assert_eq!(tags, from_slice_strict_with::<Vec<String>>(&buf, &cfg).unwrap());
+
+    let exceeded = ErrorKind::LimitExceeded {
+        limit: "max_bytes",
+        max: buf.len() as u64 - 1,
+    };
+
+    let short = max_bytes(buf.len() as u64 - 1);
+    let err = from_slice_with::<Vec<String>>(&buf, &short).unwrap_err();
+    assert_eq!(exceeded, *err.kind());
+
+    let err = from_slice_strict_with::<Vec<String>>(&buf, &short).unwrap_err();
+    assert_eq!(exceeded, *err.kind());
+
+    let cfg = max_bytes(1 << 10);
+
+    let record = Record {
+        id: 8,
+        name: "eight",
+        payload: &[],
+        note: Cow::Borrowed(""),
+        tags,
+    };
+
+    buf.clear();
+    buf.dump(&record).unwrap();
+    assert_eq!(record, from_slice_with(&buf, &cfg).unwrap());
+}
+
+#[derive(Debug, Dump, LoadBorrowed, PartialEq)]
+struct Borrows<'a> {
+    name: Option<&'a str>,
+    tags: Vec<&'a str>,
+    index: BTreeMap<&'a str, (u16, &'a [u8])>,
+    owned: Box<Owned>,
+}
+
+// usable both from a reader and nested in borrowed values
+#[derive(Debug, Dump, Load, LoadBorrowed, PartialEq)]
+struct Owned {
+    id: u32,
+    scores: Vec<f32>,
+}
+
+#[test]
+fn test_load_borrowed_containers() {
+    let owned = Owned {
+        id: 3,
+        scores: vec![0.5, 1.5],
+    };
+
+    let value = Borrows {
+        name: Some("name"),
+        tags: vec!["a", "bc"],
+        index: vec![("k", (1, &b"xy"[..])), ("l", (2, &b""[..]))].into_iter().collect(),
+        owned: Box::new(owned),
+    };
+
+    let mut buf = Vec::new();
+    buf.dump(&value).unwrap();
+
+    let loaded: Borrows = from_slice_strict(&buf).unwrap();
+    assert_eq!(value, loaded);
+
+    let range = buf.as_ptr_range();
+    assert!(range.contains(&loaded.name.unwrap().as_ptr()));
+    assert!(loaded.tags.iter().all(|tag| range.contains(&tag.as_ptr())));
+
+    // both impls of a type deriving `Load` and `LoadBorrowed` read the same bytes
+    buf.clear();
+    buf.dump(&value.owned).unwrap();
+    assert_eq!(*value.owned, (&buf[..]).load().unwrap());
+    assert_eq!(*value.owned, from_slice(&buf).unwrap());
+
+    // element paths and strict checks match those of `Load`
+    buf.clear();
+    buf.dump(&vec!["a", "b"]).unwrap();
+    *buf.last_mut().unwrap() = 1;
+
+    let err = from_slice::<Vec<&str>>(&buf).unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+    assert_eq!(Some("[1]".to_owned()), err.path());
+
+    let err = from_slice_strict::<Option<&str>>(&[2, 0]).unwrap_err();
+    assert_eq!(ErrorKind::InvalidBool { value: 2 }, *err.kind());
+
+    buf.clear();
+    buf.dump(&vec!["a", "a"]).unwrap();
+    let err = from_slice_strict::<HashSet<&str>>(&buf).unwrap_err();
+    assert_eq!(ErrorKind::DuplicateKey, *err.kind());
+    assert_eq!(Some("[1]".to_owned()), err.path());
+}
+
+#[test]
+fn test_bulk_slices() {
+    // the bulk path must give the same bytes as dumping the elements one by one
+    fn assert_bulk<T>(vals: Vec<T>)
+    where
+        T: crate::Dump + crate::Load + Copy + PartialEq + std::fmt::Debug,
+    {
+        let configs = [
+            Config::default(),
+            Config {
+                endian: Endian::Big,
+                ..Config::default()
+            },
+            Config {
+                ints: IntEncoding::Varint,
+                lengths: IntEncoding::Varint,
+                ..Config::default()
+            },
+        ];
+
+        for cfg in configs.iter() {
+            let mut expected = Vec::new();
+            expected.write_len(vals.len(), cfg).unwrap();
+
+            for val in vals.iter() {
+                expected.dump_with(cfg, val).unwrap();
+            }
+
+            let mut buf = Vec::new();
+            buf.dump_with(cfg, &vals).unwrap();
+            assert_eq!(expected, buf);
+
+            assert_eq!(vals, (&buf[..]).load_with::<Vec<T>>(cfg).unwrap());
+
+            let boxed = (&buf[..]).load_with::<Box<[T]>>(cfg).unwrap();
+            assert_eq!(&vals[..], &boxed[..]);
+        }
+    }
+
+    let mut rng = thread_rng();
+
+    // long enough to span several chunks
+    let len = 100_000;
+
+    assert_bulk((0..len).map(|_| rng.gen::<u8>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<u16>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<u32>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<u64>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<u128>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<i8>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<i16>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<i32>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<i64>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<i128>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<f32>()).collect());
+    assert_bulk((0..len).map(|_| rng.gen::<f64>()).collect());
+    assert_bulk(Vec::<u32>::new());
+
+    // canonical NaNs are still applied
+    let cfg = Config {
+        canonical_nan: true,
+        ..Config::default()
+    };
+
+    let mut buf = Vec::new();
+    buf.dump_with(&cfg, &vec![f32::from_bits(0x7F80_0001), 1.0]).unwrap();
+    assert_eq!(&[0u8, 0, 0xC0, 0x7F], &buf[8..12]);
+
+    // a lying length fails once the input runs out, without allocating for it
+    let mut lying = vec![0xFFu8; 8];
+    lying.extend_from_slice(&[1, 2, 3, 4]);
+
+    let err = (&lying[..]).load::<Vec<u64>>().unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+    assert_eq!("[0]", err.path().unwrap());
+
+    // errors point at the first element of the read that failed
+    let configs = [
+        Config::default(),
+        Config::default().with_endian(Endian::Big),
+    ];
+
+    for cfg in configs.iter() {
+        let mut buf = Vec::new();
+        buf.dump_with(cfg, &vec![7u32; 100_000]).unwrap();
+        buf.truncate(8 + 4 * 20_000);
+
+        let err = (&buf[..]).load_with::<Vec<u32>>(cfg).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+        assert_eq!("[16384]", err.path().unwrap());
+    }
+}
+
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+struct Packet {
+    #[load_dump(bytes)]
+    header: Vec<u8>,
+    #[load_dump(bytes, varint)]
+    body: Box<[u8]>,
+    trailer: Blob,
+}
+
+#[test]
+fn test_blobs() {
+    let blob = Blob(vec![0, 1, 2, 0xFF]);
+
+    // blobs are encoded like any other Vec<u8>
+    let mut buf = Vec::new();
+    buf.dump(&blob).unwrap();
+
+    let mut expected = Vec::new();
+    expected.dump(&blob.0).unwrap();
+
+    assert_eq!(expected, buf);
+    assert_eq!(blob, (&buf[..]).load().unwrap());
+
+    let packet = Packet {
+        header: vec![1, 2],
+        body: vec![3; 200].into_boxed_slice(),
+        trailer: Blob(vec![4]),
+    };
+
+    buf.clear();
+    buf.dump(&packet).unwrap();
+
+    assert_eq!(&[2u8, 0, 0, 0, 0, 0, 0, 0, 1, 2, 0xC8, 0x01], &buf[..12]);
+    assert_eq!(packet, (&buf[..]).load().unwrap());
+
+    let cfg = Config {
+        limits: Limits {
+            max_len: Some(100),
+            ..Limits::default()
+        },
+        ..Config::default()
+    };
+
+    let err = (&buf[..]).load_with::<Packet>(&cfg).unwrap_err();
+    assert_eq!(
+        ErrorKind::LimitExceeded {
+            limit: "max_len",
+            max: 100
+        },
+        *err.kind()
+    );
+
+    let err = (&[9u8, 0, 0, 0, 0, 0, 0, 0, 1][..]).load::<Blob>().unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn test_bytes_crate() {
+    use bytes::{Bytes, BytesMut};
+
+    let bytes = Bytes::from_static(b"payload");
+
+    let mut buf = Vec::new();
+    buf.dump(&bytes).unwrap();
+    assert_eq!(buf.len() as u64, serialized_size(&bytes).unwrap());
+
+    assert_eq!(Blob(b"payload".to_vec()), (&buf[..]).load().unwrap());
+    assert_eq!(bytes, (&buf[..]).load::<Bytes>().unwrap());
+    assert_eq!(&b"payload"[..], &(&buf[..]).load::<BytesMut>().unwrap()[..]);
+}
+
+// CountingReader counts the reads reaching the underlying reader.
+struct CountingReader<'a> {
+    bytes: &'a [u8],
+    reads: usize,
+}
+
+impl<'a> io::Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads += 1;
+        self.bytes.read(buf)
+    }
+}
+
+#[test]
+fn test_buffered_strings() {
+    let strings: Vec<String> = (0..100).map(|n| format!("string {}", n)).collect();
+
+    for &strings_enc in [StringEncoding::NulTerminated, StringEncoding::LengthPrefixed].iter() {
+        let cfg = Config {
+            strings: strings_enc,
+            ..Config::default()
+        };
+
+        let mut buf = Vec::new();
+        buf.dump_with(&cfg, &strings).unwrap();
+
+        let mut counting = CountingReader {
+            bytes: &buf,
+            reads: 0,
+        };
+
+        let loaded: Vec<String> = BufReader::new(&mut counting).load_with(&cfg).unwrap();
+
+        assert_eq!(strings, loaded);
+        assert!(counting.reads <= 2, "{} reads", counting.reads);
+    }
+
+    // the input ends before the NUL
+    let err = (&b"abc"[..]).load::<String>().unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+
+    let cfg = Config {
+        strings: StringEncoding::LengthPrefixed,
+        ..Config::default()
+    };
+
+    let err = (&[4u8, 0, 0, 0, 0, 0, 0, 0, b'a'][..])
+        .load_with::<String>(&cfg)
+        .unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+}
+
+#[test]
+fn test_serialized_size() {
+    fn assert_size<T: crate::Dump>(val: &T, cfg: &Config) {
+        let mut buf = Vec::new();
+        buf.dump_with(cfg, val).unwrap();
+
+        assert_eq!(buf.len() as u64, serialized_size_with(val, cfg).unwrap());
+    }
+
+    let configs = [
+        Config::default(),
+        Config {
+            endian: Endian::Big,
+            ints: IntEncoding::Varint,
+            lengths: IntEncoding::Varint,
+            strings: StringEncoding::LengthPrefixed,
+            tags: TagEncoding::Varint,
+            ..Config::default()
+        },
+        Config {
+            tags: TagEncoding::U16,
+            canonical_nan: true,
+            ..Config::default()
+        },
+    ];
+
+    for cfg in configs.iter() {
+        assert_size(&Sample::get(), cfg);
+        assert_size(&(0u8, -1i16, 300u32, i64::MIN, u128::MAX), cfg);
+        assert_size(&[Some('x'), None, Some('\u{10FFFF}')], cfg);
+        assert_size(&(usize::MAX, -1isize, std::num::NonZeroU64::new(1 << 40)), cfg);
+        assert_size(&vec![1.5f32, f32::NAN], cfg);
+        assert_size(&vec![vec![1u64, 1 << 60], vec![]], cfg);
+        assert_size(&(0..200).collect::<BTreeSet<i32>>(), cfg);
+        assert_size(&Box::new(()), cfg);
+        assert_size(&[Tagged::Seven, Tagged::Eight(3), Tagged::Two { val: 9 }], cfg);
+        assert_size(&[SmallTag::A, SmallTag::B(7)], cfg);
+        assert_size(&[VarTag::Small, VarTag::Large(1)], cfg);
+        assert_size(
+            &Partial {
+                val: "partial".to_owned(),
+                state: Opaque,
+            },
+            cfg,
+        );
+        assert_size(
+            &Packet {
+                header: vec![1, 2],
+                body: vec![3; 200].into_boxed_slice(),
+                trailer: Blob(vec![4]),
+            },
+            cfg,
+        );
+        assert_size(
+            &Header {
+                magic: 1,
+                version: 2,
+                payload: vec![3],
+            },
+            cfg,
+        );
+        assert_size(
+            &Ids {
+                small: vec![1, 1000],
+                fixed: 3,
+            },
+            cfg,
+        );
+        assert_size(&Delta::Jump(Cache, -100), cfg);
+        assert_size(
+            &Record {
+                id: 1,
+                name: "name",
+                payload: b"payload",
+                note: Cow::Borrowed("note"),
+                tags: vec!["tag".into()],
+            },
+            cfg,
+        );
+    }
+
+    assert_eq!(8 + 4 * 3, serialized_size(&vec![1i32, 2, 3]).unwrap());
+    assert_eq!(4, serialized_size(&"abc".to_owned()).unwrap());
+
+    // sizes fail where dumping would
+    let err = serialized_size(&"a\0b").unwrap_err();
+    assert_eq!(ErrorKind::InteriorNul, *err.kind());
+
+    let cfg = Config {
+        tags: TagEncoding::U8,
+        ..Config::default()
+    };
+
+    let err = serialized_size_with(&Wide::Large, &cfg).unwrap_err();
+    assert_eq!(ErrorKind::TagOverflow { value: 1000 }, *err.kind());
+
+    let err = serialized_size(&Connection::Open(1)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::NotSerializable { .. }));
+}
+
+#[test]
+fn test_error_source() {
+    use std::error::Error as _;
+
+    // a truncated read keeps the io::Error that caused it
+    let err = (&[1u8, 2][..]).load::<u32>().unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+
+    let io_err = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(io::ErrorKind::UnexpectedEof, io_err.kind());
+
+    let err = (&[3u8, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xfe, 0xfd][..])
+        .load_with::<String>(&Config {
+            strings: StringEncoding::LengthPrefixed,
+            ..Config::default()
+        })
+        .unwrap_err();
+    assert_eq!(ErrorKind::InvalidUtf8, *err.kind());
+    assert!(err.source().unwrap().is::<std::string::FromUtf8Error>());
+
+    // errors found by this crate have no source
+    let err = serialized_size(&"a\0b").unwrap_err();
+    assert!(err.source().is_none());
+    assert_eq!("NUL byte inside a NUL-terminated string", err.to_string());
+
+    // Error converts into boxed std errors through `?`
+    fn load_boxed(mut bytes: &[u8]) -> std::result::Result<u32, Box<dyn std::error::Error>> {
+        Ok(bytes.load::<u32>()?)
+    }
+
+    assert_eq!(7, load_boxed(&[7, 0, 0, 0]).unwrap());
+    assert_eq!(
+        "Input ends in the middle of a value (byte 1)",
+        load_boxed(&[7]).unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn test_error_location() {
+    use SampleEnum::*;
+
+    let sample = Sample {
+        some_box_arr: vec![1, 2].into(),
+        some_enum_arr: [
+            One,
+            Struct {
+                f1: 5,
+                f2: "ok".to_owned(),
+            },
+            One,
+        ],
+        some_vec: vec!["one".into()],
+        some_hmap: HashMap::new(),
+        some_tmap: BTreeMap::new(),
+    };
+
+    let mut buf = Vec::new();
+    buf.dump(&sample).unwrap();
+
+    // box length and elements, the tag of `One`, then the tag and `f1` of `Struct`
+    let f2 = 8 + 2 * 4 + 4 + 4 + 4;
+    assert_eq!(b"ok\0", &buf[f2..f2 + 3]);
+
+    buf[f2] = 0xff;
+
+    let err = (&buf[..]).load::<Sample>().unwrap_err();
+    assert_eq!(ErrorKind::InvalidUtf8, *err.kind());
+    assert_eq!(Some((f2 + 3) as u64), err.offset());
+    assert_eq!("Sample.some_enum_arr[1].Struct.f2", err.path().unwrap());
+    assert_eq!(
+        format!(
+            "Invalid UTF-8 detected in input (Sample.some_enum_arr[1].Struct.f2, byte {})",
+            f2 + 3
+        ),
+        err.to_string()
+    );
+
+    // collections of derived types give the index first
+    let mut eithers = Vec::new();
+    eithers
+        .dump(&vec![Either::<u32, u32>::Left(1), Either::Right(2)])
+        .unwrap();
+
+    let err = (&eithers[..eithers.len() - 1])
+        .load::<Vec<Either<u32, u32>>>()
+        .unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+    assert_eq!(Some(eithers.len() as u64 - 1), err.offset());
+    assert_eq!("[1].Right.0", err.path().unwrap());
+
+    // elements of top-level arrays and tuples share the offset of the whole value
+    let strict = Config {
+        strict: true,
+        ..Config::default()
+    };
+
+    let err = (&[1u8, 0, 1, 2][..])
+        .load_with::<[bool; 4]>(&strict)
+        .unwrap_err();
+    assert_eq!(Some(4), err.offset());
+    assert_eq!("[3]", err.path().unwrap());
+
+    let err = (&[7u8, 0, 0, 0, 1, 9][..])
+        .load_with::<(u32, Option<bool>)>(&strict)
+        .unwrap_err();
+    assert_eq!(ErrorKind::InvalidBool { value: 9 }, *err.kind());
+    assert_eq!(Some(6), err.offset());
+
+    // errors of the type itself, such as a bad tag
+    let err = (&[9u8, 0, 0, 0][..]).load::<SampleEnum>().unwrap_err();
+    assert_eq!("SampleEnum", err.path().unwrap());
+    assert_eq!(Some(4), err.offset());
+
+    // going over the byte limit keeps the location
+    let cfg = Config {
+        limits: Limits {
+            max_bytes: Some(f2 as u64),
+            ..Limits::default()
+        },
+        ..Config::default()
+    };
+
+    let err = (&buf[..]).load_with::<Sample>(&cfg).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::LimitExceeded { .. }));
+    assert_eq!("Sample.some_enum_arr[1].Struct.f2", err.path().unwrap());
+
+    // zero-copy loads count the bytes consumed from the slice
+    let mut buf = Vec::new();
+    buf.dump(&5u32).unwrap();
+    buf.dump(&"name").unwrap();
+    buf.dump(&vec![0xffu8]).unwrap();
+
+    let err = from_slice::<Record>(&buf).unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+    assert_eq!(Some(buf.len() as u64), err.offset());
+    assert_eq!("Record.note", err.path().unwrap());
+}
+
+#[test]
+fn test_try_load() {
+    use SampleEnum::*;
+
+    let records = vec![
+        One,
+        Struct {
+            f1: 1,
+            f2: "two".to_owned(),
+        },
+        Tuple(3, true, Box::new(One)),
+    ];
+
+    let mut buf = Vec::new();
+
+    for rec in &records {
+        buf.dump(rec).unwrap();
+    }
+
+    let mut read = BufReader::with_capacity(3, &buf[..]);
+    let mut loaded = Vec::new();
+
+    while let Some(rec) = read.try_load::<SampleEnum>().unwrap() {
+        loaded.push(rec);
+    }
+
+    assert_eq!(records, loaded);
+
+    // a record cut in half is not the end of the stream
+    let mut read = &buf[..buf.len() - 1];
+
+    assert_eq!(Some(One), read.try_load().unwrap());
+    assert!(read.try_load::<SampleEnum>().unwrap().is_some());
+
+    let err = read.try_load::<SampleEnum>().unwrap_err();
+    assert_eq!(ErrorKind::UnexpectedEof, *err.kind());
+
+    // other read errors are still reported as such
+    let err = BufReader::new(FailingReader).load::<u32>().unwrap_err();
+    assert_eq!(ErrorKind::FileRead, *err.kind());
+
+    let err = BufReader::new(FailingReader).try_load::<u32>().unwrap_err();
+    assert_eq!(ErrorKind::FileRead, *err.kind());
+}
+
+struct FailingReader;
+
+impl io::Read for FailingReader {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("broken pipe"))
+    }
+}
+
+#[test]
+fn test_strict() {
+    let strict = Config {
+        strict: true,
+        ..Config::default()
+    };
+
+    // lenient loads take any nonzero byte as true
+    assert!((&[2u8][..]).load::<bool>().unwrap());
+    assert_eq!(Some(7u8), (&[2u8, 7][..]).load().unwrap());
+
+    let err = (&[2u8][..]).load_with::<bool>(&strict).unwrap_err();
+    assert_eq!(ErrorKind::InvalidBool { value: 2 }, *err.kind());
+
+    let err = (&[2u8, 7][..])
+        .load_with::<Option<u8>>(&strict)
+        .unwrap_err();
+    assert_eq!(ErrorKind::InvalidBool { value: 2 }, *err.kind());
+
+    assert!((&[1u8][..]).load_with::<bool>(&strict).unwrap());
+    assert_eq!(None, (&[0u8][..]).load_with::<Option<u8>>(&strict).unwrap());
+
+    // derived types are strict all the way down
+    let mut buf = Vec::new();
+    buf.dump(&SampleEnum::Tuple(1, true, Box::new(SampleEnum::One)))
+        .unwrap();
+    buf[4 + 16] = 0xff;
+
+    let err = (&buf[..]).load_exact::<SampleEnum>().unwrap_err();
+    assert_eq!(ErrorKind::InvalidBool { value: 0xff }, *err.kind());
+    assert_eq!("SampleEnum.Tuple.1", err.path().unwrap());
+
+    // exact loads must take up the whole input
+    let mut buf = Vec::new();
+    buf.dump(&(1u32, false)).unwrap();
+
+    assert_eq!((1u32, false), (&buf[..]).load_exact().unwrap());
+    assert_eq!((1u32, false), from_slice_strict(&buf).unwrap());
+
+    buf.push(0);
+
+    let err = (&buf[..]).load_exact::<(u32, bool)>().unwrap_err();
+    assert_eq!(ErrorKind::TrailingBytes, *err.kind());
+    assert_eq!(Some(5), err.offset());
+
+    let err = from_slice_strict::<(u32, bool)>(&buf).unwrap_err();
+    assert_eq!(ErrorKind::TrailingBytes, *err.kind());
+    assert_eq!(Some(5), err.offset());
+
+    // a plain load just stops at the end of the value
+    assert_eq!((1u32, false), (&buf[..]).load().unwrap());
+}
+
+#[test]
+fn test_canonical() {
+    let canonical = Config {
+        canonical: true,
+        ..Config::default()
+    };
+
+    // same entries, inserted in opposite orders
+    let forward: HashMap<u32, String> = (0..300).map(|n| (n, n.to_string())).collect();
+    let backward: HashMap<u32, String> = (0..300).rev().map(|n| (n, n.to_string())).collect();
+
+    let mut buf = Vec::new();
+    buf.dump_with(&canonical, &forward).unwrap();
+
+    let mut other = Vec::new();
+    other.dump_with(&canonical, &backward).unwrap();
+
+    assert_eq!(buf, other);
+    assert_eq!(forward, (&buf[..]).load().unwrap());
+    assert_eq!(serialized_size(&forward).unwrap(), buf.len() as u64);
+
+    // sorted by bytes rather than by value: 256 is 00 01 00 00 in little endian
+    let set: HashSet<u32> = vec![1, 256, 2].into_iter().collect();
+
+    buf.clear();
+    buf.dump_with(&canonical, &set).unwrap();
+
+    assert_eq!(
+        &[3, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0][..],
+        &buf[..]
+    );
+
+    // nested in derived types, loading and dumping again gives the same bytes
+    buf.clear();
+    buf.dump_with(&canonical, &Sample::get()).unwrap();
+
+    let copy: Sample = (&buf[..]).load().unwrap();
+
+    other.clear();
+    other.dump_with(&canonical, &copy).unwrap();
+
+    assert_eq!(buf, other);
+}
+
+#[test]
+fn test_duplicate_keys() {
+    let strict = Config {
+        strict: true,
+        ..Config::default()
+    };
+
+    let mut buf = Vec::new();
+    buf.dump(&vec![(1u32, 10u8), (2, 20), (1, 30)]).unwrap();
+
+    // lenient loads keep the last value
+    let map: BTreeMap<u32, u8> = (&buf[..]).load().unwrap();
+    assert_eq!(Some(&30), map.get(&1));
+
+    let map: HashMap<u32, u8> = (&buf[..]).load().unwrap();
+    assert_eq!(2, map.len());
+
+    let err = (&buf[..])
+        .load_with::<BTreeMap<u32, u8>>(&strict)
+        .unwrap_err();
+    assert_eq!(ErrorKind::DuplicateKey, *err.kind());
+    assert_eq!("[2]", err.path().unwrap());
+
+    let err = from_slice_strict::<HashMap<u32, u8>>(&buf).unwrap_err();
+    assert_eq!(ErrorKind::DuplicateKey, *err.kind());
+    assert_eq!(Some(buf.len() as u64), err.offset());
+
+    buf.clear();
+    buf.dump(&vec!["a", "b", "a"]).unwrap();
+
+    assert_eq!(2, (&buf[..]).load::<HashSet<String>>().unwrap().len());
+
+    let err = (&buf[..]).load_exact::<HashSet<String>>().unwrap_err();
+    assert_eq!(ErrorKind::DuplicateKey, *err.kind());
+
+    let err = (&buf[..]).load_exact::<BTreeSet<String>>().unwrap_err();
+    assert_eq!(ErrorKind::DuplicateKey, *err.kind());
+}
+
+// Command has fields named like the parameters and locals of the generated methods.
+#[derive(Debug, Dump, Eq, Load, PartialEq)]
+enum Command {
+    Configure { cfg: u32, write: bool },
+    Resize { size: u64 },
+}
+
+#[test]
+fn test_shadowing_field_names() {
+    let cmd = Command::Configure {
+        cfg: 7,
+        write: true,
+    };
+
+    assert_eq!(cmd, round_trip(&cmd));
+    assert_eq!(4 + 4 + 1, serialized_size(&cmd).unwrap());
+
+    let cmd = Command::Resize { size: 1 << 40 };
+
+    assert_eq!(cmd, round_trip(&cmd));
+    assert_eq!(4 + 8, serialized_size(&cmd).unwrap());
+}
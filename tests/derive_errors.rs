@@ -0,0 +1,6 @@
+// Misuse of the derives must give a compile error pointing at the offending code, rather than
+// a panic of the derive. The expected errors are in the `.stderr` files next to each case.
+#[test]
+fn derive_errors() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
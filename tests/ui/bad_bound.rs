@@ -0,0 +1,9 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+#[load_dump(bound = "T: +")]
+struct Wrapper<T> {
+    inner: T,
+}
+
+fn main() {}
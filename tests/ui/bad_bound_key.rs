@@ -0,0 +1,9 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+#[load_dump(bound(dumb = "T: Clone"))]
+struct Wrapper<T> {
+    inner: T,
+}
+
+fn main() {}
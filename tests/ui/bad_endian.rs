@@ -0,0 +1,9 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+struct Point {
+    #[load_dump(endian = "middle")]
+    x: u32,
+}
+
+fn main() {}
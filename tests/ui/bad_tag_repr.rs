@@ -0,0 +1,10 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+#[load_dump(tag_repr = "u7")]
+enum Shape {
+    Circle,
+    Square,
+}
+
+fn main() {}
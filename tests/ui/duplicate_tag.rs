@@ -0,0 +1,12 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+enum Shape {
+    #[load_dump(tag = 1)]
+    Circle,
+    Square,
+    #[load_dump(tag = 2)]
+    Triangle,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+enum Shape {
+    #[load_dump(panic)]
+    Circle,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+#[load_dump(tag_repr = "u8")]
+enum Shape {
+    Circle,
+    #[load_dump(tag = 256)]
+    Square,
+}
+
+fn main() {}
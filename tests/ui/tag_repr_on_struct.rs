@@ -0,0 +1,9 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+#[load_dump(tag_repr = "u8")]
+struct Point {
+    x: u32,
+}
+
+fn main() {}
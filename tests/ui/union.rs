@@ -0,0 +1,9 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+union Bits {
+    int: u32,
+    float: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+struct Point {
+    #[load_dump(skp)]
+    x: u32,
+    y: u32,
+}
+
+fn main() {}
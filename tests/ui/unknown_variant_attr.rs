@@ -0,0 +1,10 @@
+use load_dump_derive::{Dump, Load};
+
+#[derive(Dump, Load)]
+enum Shape {
+    #[load_dump(tga = 1)]
+    Circle,
+    Square,
+}
+
+fn main() {}